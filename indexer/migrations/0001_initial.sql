@@ -0,0 +1,114 @@
+-- schema iniziale: blocchi, transazioni, ricevute, log e reorg per ogni chain.
+-- IF NOT EXISTS perche' i database creati a mano dal vecchio README hanno gia' queste tabelle
+
+-- le prime versioni del README creavano blocks e indexer_state senza chain_id (una sola riga di stato
+-- con id = 1). Quell'indexer usava solo Alchemy su Sepolia, quindi le righe esistenti sono di 11155111
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM information_schema.tables WHERE table_schema = current_schema() AND table_name = 'blocks')
+       AND NOT EXISTS (SELECT 1 FROM information_schema.columns WHERE table_schema = current_schema() AND table_name = 'blocks' AND column_name = 'chain_id') THEN
+        ALTER TABLE blocks ADD COLUMN chain_id BIGINT NOT NULL DEFAULT 11155111;
+        ALTER TABLE blocks ALTER COLUMN chain_id DROP DEFAULT;
+        ALTER TABLE blocks DROP CONSTRAINT blocks_pkey;
+        ALTER TABLE blocks ADD PRIMARY KEY (chain_id, number);
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM information_schema.columns WHERE table_schema = current_schema() AND table_name = 'indexer_state' AND column_name = 'id')
+       AND NOT EXISTS (SELECT 1 FROM information_schema.columns WHERE table_schema = current_schema() AND table_name = 'indexer_state' AND column_name = 'chain_id') THEN
+        ALTER TABLE indexer_state ADD COLUMN chain_id BIGINT;
+        UPDATE indexer_state SET chain_id = 11155111 WHERE id = 1;
+        DELETE FROM indexer_state WHERE chain_id IS NULL;
+        ALTER TABLE indexer_state DROP CONSTRAINT indexer_state_pkey;
+        ALTER TABLE indexer_state DROP COLUMN id;
+        ALTER TABLE indexer_state ADD PRIMARY KEY (chain_id);
+    END IF;
+END $$;
+
+CREATE TABLE IF NOT EXISTS blocks (
+    chain_id BIGINT NOT NULL,
+    number BIGINT NOT NULL,
+    hash VARCHAR(66) NOT NULL,
+    parent_hash VARCHAR(66) NOT NULL,
+    timestamp BIGINT NOT NULL,
+    miner VARCHAR(42) NOT NULL,
+    gas_used BIGINT NOT NULL,
+    gas_limit BIGINT NOT NULL,
+    transactions_count INTEGER NOT NULL,
+    size BIGINT NOT NULL,
+    PRIMARY KEY (chain_id, number)
+);
+
+CREATE TABLE IF NOT EXISTS indexer_state (
+    chain_id BIGINT PRIMARY KEY,
+    last_block_indexed BIGINT NOT NULL,
+    last_update TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    chain_id BIGINT NOT NULL,
+    hash VARCHAR(66) NOT NULL,
+    block_number BIGINT NOT NULL,
+    transaction_index INTEGER NOT NULL,
+    from_address VARCHAR(42) NOT NULL,
+    to_address VARCHAR(42),
+    value NUMERIC(78,0) NOT NULL,
+    nonce BIGINT NOT NULL,
+    gas BIGINT NOT NULL,
+    gas_price NUMERIC(78,0),
+    max_fee_per_gas NUMERIC(78,0),
+    max_priority_fee_per_gas NUMERIC(78,0),
+    input TEXT NOT NULL,
+    tx_type SMALLINT,
+    tx_chain_id BIGINT,
+    PRIMARY KEY (chain_id, hash),
+    FOREIGN KEY (chain_id, block_number) REFERENCES blocks(chain_id, number)
+);
+
+CREATE INDEX IF NOT EXISTS transactions_block_number_idx ON transactions (chain_id, block_number);
+CREATE INDEX IF NOT EXISTS transactions_from_address_idx ON transactions (chain_id, from_address);
+CREATE INDEX IF NOT EXISTS transactions_to_address_idx ON transactions (chain_id, to_address);
+
+CREATE TABLE IF NOT EXISTS receipts (
+    chain_id BIGINT NOT NULL,
+    transaction_hash VARCHAR(66) NOT NULL,
+    block_number BIGINT NOT NULL,
+    status SMALLINT,
+    gas_used BIGINT NOT NULL,
+    cumulative_gas_used BIGINT NOT NULL,
+    effective_gas_price NUMERIC(78,0),
+    contract_address VARCHAR(42),
+    PRIMARY KEY (chain_id, transaction_hash),
+    FOREIGN KEY (chain_id, transaction_hash) REFERENCES transactions(chain_id, hash),
+    FOREIGN KEY (chain_id, block_number) REFERENCES blocks(chain_id, number)
+);
+
+CREATE TABLE IF NOT EXISTS logs (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    log_index INTEGER NOT NULL,
+    transaction_hash VARCHAR(66) NOT NULL,
+    address VARCHAR(42) NOT NULL,
+    topic0 VARCHAR(66),
+    topic1 VARCHAR(66),
+    topic2 VARCHAR(66),
+    topic3 VARCHAR(66),
+    data TEXT NOT NULL,
+    PRIMARY KEY (chain_id, block_number, log_index),
+    FOREIGN KEY (chain_id, transaction_hash) REFERENCES transactions(chain_id, hash),
+    FOREIGN KEY (chain_id, block_number) REFERENCES blocks(chain_id, number)
+);
+
+CREATE INDEX IF NOT EXISTS logs_address_idx ON logs (chain_id, address);
+CREATE INDEX IF NOT EXISTS logs_topic0_idx ON logs (chain_id, topic0);
+CREATE INDEX IF NOT EXISTS logs_transaction_hash_idx ON logs (chain_id, transaction_hash);
+
+CREATE TABLE IF NOT EXISTS reorgs (
+    id BIGSERIAL PRIMARY KEY,
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    orphaned_hash VARCHAR(66) NOT NULL,
+    canonical_hash VARCHAR(66) NOT NULL,
+    common_ancestor BIGINT NOT NULL,
+    depth BIGINT NOT NULL,
+    detected_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,14 @@
+-- blocchi che non sono stati indicizzati dopo tutti i tentativi, ripresi da un task in background
+
+CREATE TABLE IF NOT EXISTS failed_blocks (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    first_failed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_attempt_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    next_retry_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (chain_id, block_number)
+);
+
+CREATE INDEX IF NOT EXISTS failed_blocks_next_retry_idx ON failed_blocks (chain_id, next_retry_at);
@@ -0,0 +1,34 @@
+-- eventi ERC-20 decodificati dai log, importi uint256 esatti
+
+CREATE TABLE IF NOT EXISTS erc20_transfers (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    log_index INTEGER NOT NULL,
+    transaction_hash VARCHAR(66) NOT NULL,
+    token VARCHAR(42) NOT NULL,
+    from_address VARCHAR(42) NOT NULL,
+    to_address VARCHAR(42) NOT NULL,
+    value NUMERIC(78,0) NOT NULL,
+    PRIMARY KEY (chain_id, block_number, log_index),
+    FOREIGN KEY (chain_id, block_number) REFERENCES blocks(chain_id, number)
+);
+
+CREATE INDEX IF NOT EXISTS erc20_transfers_token_idx ON erc20_transfers (chain_id, token);
+CREATE INDEX IF NOT EXISTS erc20_transfers_from_idx ON erc20_transfers (chain_id, from_address);
+CREATE INDEX IF NOT EXISTS erc20_transfers_to_idx ON erc20_transfers (chain_id, to_address);
+
+CREATE TABLE IF NOT EXISTS erc20_approvals (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    log_index INTEGER NOT NULL,
+    transaction_hash VARCHAR(66) NOT NULL,
+    token VARCHAR(42) NOT NULL,
+    owner_address VARCHAR(42) NOT NULL,
+    spender_address VARCHAR(42) NOT NULL,
+    value NUMERIC(78,0) NOT NULL,
+    PRIMARY KEY (chain_id, block_number, log_index),
+    FOREIGN KEY (chain_id, block_number) REFERENCES blocks(chain_id, number)
+);
+
+CREATE INDEX IF NOT EXISTS erc20_approvals_token_idx ON erc20_approvals (chain_id, token);
+CREATE INDEX IF NOT EXISTS erc20_approvals_owner_idx ON erc20_approvals (chain_id, owner_address);
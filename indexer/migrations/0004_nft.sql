@@ -0,0 +1,35 @@
+-- trasferimenti ERC-721 / ERC-1155 e proprieta' corrente dei token
+
+CREATE TABLE IF NOT EXISTS nft_transfers (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    log_index INTEGER NOT NULL,
+    batch_index INTEGER NOT NULL,
+    transaction_hash VARCHAR(66) NOT NULL,
+    contract VARCHAR(42) NOT NULL,
+    standard VARCHAR(8) NOT NULL,
+    operator VARCHAR(42),
+    from_address VARCHAR(42) NOT NULL,
+    to_address VARCHAR(42) NOT NULL,
+    token_id NUMERIC(78,0) NOT NULL,
+    amount NUMERIC(78,0) NOT NULL,
+    PRIMARY KEY (chain_id, block_number, log_index, batch_index),
+    FOREIGN KEY (chain_id, block_number) REFERENCES blocks(chain_id, number)
+);
+
+CREATE INDEX IF NOT EXISTS nft_transfers_token_idx ON nft_transfers (chain_id, contract, token_id);
+CREATE INDEX IF NOT EXISTS nft_transfers_from_idx ON nft_transfers (chain_id, from_address);
+CREATE INDEX IF NOT EXISTS nft_transfers_to_idx ON nft_transfers (chain_id, to_address);
+
+-- saldo corrente per (contratto, token, proprietario): 1 per gli ERC-721, quantita' per gli ERC-1155.
+-- aggiornato a ogni trasferimento e riportato indietro quando un reorg cancella i trasferimenti
+CREATE TABLE IF NOT EXISTS nft_owners (
+    chain_id BIGINT NOT NULL,
+    contract VARCHAR(42) NOT NULL,
+    token_id NUMERIC(78,0) NOT NULL,
+    owner VARCHAR(42) NOT NULL,
+    balance NUMERIC(78,0) NOT NULL,
+    PRIMARY KEY (chain_id, contract, token_id, owner)
+);
+
+CREATE INDEX IF NOT EXISTS nft_owners_owner_idx ON nft_owners (chain_id, owner);
@@ -0,0 +1,18 @@
+-- contratti creati, con il bytecode runtime e il suo hash per raggruppare i deploy identici
+
+CREATE TABLE IF NOT EXISTS contracts (
+    chain_id BIGINT NOT NULL,
+    address VARCHAR(42) NOT NULL,
+    block_number BIGINT NOT NULL,
+    transaction_hash VARCHAR(66) NOT NULL,
+    deployer VARCHAR(42) NOT NULL,
+    creation_type VARCHAR(8) NOT NULL,
+    bytecode TEXT NOT NULL,
+    bytecode_hash VARCHAR(66) NOT NULL,
+    PRIMARY KEY (chain_id, address, block_number),
+    FOREIGN KEY (chain_id, block_number) REFERENCES blocks(chain_id, number)
+);
+
+CREATE INDEX IF NOT EXISTS contracts_bytecode_hash_idx ON contracts (chain_id, bytecode_hash);
+CREATE INDEX IF NOT EXISTS contracts_deployer_idx ON contracts (chain_id, deployer);
+CREATE INDEX IF NOT EXISTS contracts_block_number_idx ON contracts (chain_id, block_number);
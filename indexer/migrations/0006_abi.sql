@@ -0,0 +1,24 @@
+-- ABI registrati per indirizzo e log decodificati con quegli ABI
+
+CREATE TABLE IF NOT EXISTS abis (
+    chain_id BIGINT NOT NULL,
+    address VARCHAR(42) NOT NULL,
+    abi JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (chain_id, address)
+);
+
+CREATE TABLE IF NOT EXISTS decoded_events (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    log_index INTEGER NOT NULL,
+    transaction_hash VARCHAR(66) NOT NULL,
+    contract VARCHAR(42) NOT NULL,
+    event_name TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    params JSONB NOT NULL,
+    PRIMARY KEY (chain_id, block_number, log_index),
+    FOREIGN KEY (chain_id, block_number) REFERENCES blocks(chain_id, number)
+);
+
+CREATE INDEX IF NOT EXISTS decoded_events_contract_idx ON decoded_events (chain_id, contract, event_name);
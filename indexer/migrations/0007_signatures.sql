@@ -0,0 +1,14 @@
+-- registro locale delle firme di funzione e metodo chiamato da ogni transazione
+
+CREATE TABLE IF NOT EXISTS function_signatures (
+    selector VARCHAR(10) NOT NULL,
+    signature TEXT NOT NULL,
+    source VARCHAR(8) NOT NULL,
+    PRIMARY KEY (selector, signature)
+);
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS method_name TEXT;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS method_signature TEXT;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS method_args JSONB;
+
+CREATE INDEX IF NOT EXISTS transactions_method_idx ON transactions (chain_id, to_address, method_name);
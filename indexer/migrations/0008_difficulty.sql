@@ -0,0 +1,5 @@
+-- difficolta' dei blocchi: quantita' fino a 256 bit, quindi NUMERIC(78,0) come gli importi.
+-- Nulle se il nodo non le restituisce (totalDifficulty non c'e' piu' dopo il merge su molti client)
+
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS difficulty NUMERIC(78,0);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS total_difficulty NUMERIC(78,0);
@@ -0,0 +1,17 @@
+-- resto dell'header dei blocchi (post-merge, Shanghai, Cancun, Prague), per le analisi sulle fee e
+-- per poter ricalcolare l'hash del blocco. Nulle per i blocchi salvati prima di questa migrazione
+-- e per i campi introdotti dopo il blocco (withdrawals_root, blob_gas_used, requests_hash...)
+
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS state_root VARCHAR(66);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS transactions_root VARCHAR(66);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS receipts_root VARCHAR(66);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS logs_bloom TEXT;
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS extra_data TEXT;
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS mix_hash VARCHAR(66);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS nonce VARCHAR(18);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS base_fee_per_gas NUMERIC(78,0);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS withdrawals_root VARCHAR(66);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS blob_gas_used BIGINT;
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS excess_blob_gas BIGINT;
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS parent_beacon_block_root VARCHAR(66);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS requests_hash VARCHAR(66);
@@ -0,0 +1,24 @@
+-- campi che servono al proxy JSON-RPC per rispondere come il nodo a eth_getBlockBy*,
+-- eth_getTransactionByHash e eth_getTransactionReceipt. uncles e' sempre valorizzato (anche [])
+-- per i blocchi salvati dopo questa migrazione: NULL vuol dire blocco salvato prima, con transazioni
+-- e ricevute incomplete, che il proxy inoltra al nodo finche' non viene reindicizzato
+
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS sha3_uncles VARCHAR(66);
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS uncles JSONB;
+ALTER TABLE blocks ADD COLUMN IF NOT EXISTS withdrawals JSONB;
+
+-- firma (v/r/s sono quantita' fino a 256 bit) e campi dei tipi 1, 3 e 4
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS v NUMERIC(78,0);
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS r NUMERIC(78,0);
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS s NUMERIC(78,0);
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS y_parity SMALLINT;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS access_list JSONB;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS max_fee_per_blob_gas NUMERIC(78,0);
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS blob_versioned_hashes JSONB;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS authorization_list JSONB;
+
+-- root al posto di status per le ricevute pre-Byzantium, blob gas per le transazioni di tipo 3
+ALTER TABLE receipts ADD COLUMN IF NOT EXISTS logs_bloom TEXT;
+ALTER TABLE receipts ADD COLUMN IF NOT EXISTS root VARCHAR(66);
+ALTER TABLE receipts ADD COLUMN IF NOT EXISTS blob_gas_used BIGINT;
+ALTER TABLE receipts ADD COLUMN IF NOT EXISTS blob_gas_price NUMERIC(78,0);
@@ -0,0 +1,160 @@
+use ethabi::{Contract, Hash, ParamType, RawLog, Token};
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use std::path::Path;
+use crate::error::IndexerError;
+use crate::models::Log;
+use crate::signatures::DecodedCall;
+use crate::utils::{bytes_to_hex, hex_to_bytes};
+use tracing::warn;
+
+
+//evento decodificato con l'ABI del contratto: nome, firma e parametri per nome
+pub struct DecodedEvent {
+    pub name: String,
+    pub signature: String,
+    pub params: Value,
+}
+
+
+//carico gli ABI dalla cartella ABI_DIR nella tabella abis. Ogni file si chiama <address>.json e
+//contiene l'ABI (array) oppure un artifact di Hardhat/Foundry con il campo "abi".
+//restituisce quanti ABI sono stati registrati
+pub async fn load_dir(pool: &PgPool, chain_id: i64, dir: &Path) -> Result<usize, IndexerError> {
+    let mut loaded = 0;
+    let read_error = |e: std::io::Error| IndexerError::Config(format!("cannot read ABI_DIR {}: {}", dir.display(), e));
+    
+    for entry in std::fs::read_dir(dir).map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        
+        let address = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(stem) if stem.len() == 42 && stem.starts_with("0x") => stem.to_lowercase(),
+            _ => {
+                warn!(file = %path.display(), "skipping ABI file: name is not an address");
+                continue;
+            }
+        };
+        
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| IndexerError::Config(format!("cannot read {}: {}", path.display(), e)))?;
+        let content: Value = serde_json::from_str(&text)
+            .map_err(|e| IndexerError::Config(format!("invalid JSON in {}: {}", path.display(), e)))?;
+        let abi = match content.get("abi") {
+            Some(abi) => abi.clone(),
+            None => content,
+        };
+        
+        //controllo subito che sia un ABI valido, cosi' l'errore esce all'avvio e non durante il sync
+        let contract = parse(&abi).map_err(|e| IndexerError::Config(format!("invalid ABI in {}: {}", path.display(), e)))?;
+        
+        crate::db::save_abi(pool, chain_id, &address, &abi).await?;
+        crate::db::save_signatures(pool, &function_signatures(&contract), "abi").await?;
+        loaded += 1;
+    }
+    
+    Ok(loaded)
+}
+
+
+pub fn parse(abi: &Value) -> Result<Contract, IndexerError> {
+    Ok(serde_json::from_value(abi.clone())?)
+}
+
+
+//decodifico un log con l'ABI del suo contratto, None se nessun evento corrisponde al topic0
+//(gli eventi anonimi non hanno topic0 e non si possono riconoscere)
+pub fn decode_log(contract: &Contract, log: &Log) -> Result<Option<DecodedEvent>, IndexerError> {
+    let topics = log.topics.iter()
+        .map(|t| Ok(Hash::from_slice(&hex_to_bytes(t)?)))
+        .collect::<Result<Vec<Hash>, IndexerError>>()?;
+    
+    let topic0 = match topics.first() {
+        Some(topic0) => *topic0,
+        None => return Ok(None),
+    };
+    
+    let event = match contract.events().find(|e| !e.anonymous && e.signature() == topic0) {
+        Some(event) => event,
+        None => return Ok(None),
+    };
+    
+    //se il log non rispetta l'ABI (es. parametri indicizzati diversi) lo salto invece di fallire il blocco
+    let parsed = match event.parse_log(RawLog { topics, data: hex_to_bytes(&log.data)? }) {
+        Ok(parsed) => parsed,
+        Err(_) => return Ok(None),
+    };
+    
+    let mut params = Map::new();
+    for (i, param) in parsed.params.into_iter().enumerate() {
+        let name = if param.name.is_empty() { format!("arg{}", i) } else { param.name };
+        params.insert(name, token_to_json(param.value));
+    }
+    
+    Ok(Some(DecodedEvent {
+        name: event.name.clone(),
+        signature: format_signature(&event.name, event.inputs.iter().map(|p| &p.kind)),
+        params: Value::Object(params),
+    }))
+}
+
+
+//firma leggibile, es. Transfer(address,address,uint256)
+fn format_signature<'a>(name: &str, kinds: impl Iterator<Item = &'a ParamType>) -> String {
+    let types: Vec<String> = kinds.map(|kind| kind.to_string()).collect();
+    format!("{}({})", name, types.join(","))
+}
+
+
+//firme delle funzioni dell'ABI, da aggiungere al registro dei selettori
+pub fn function_signatures(contract: &Contract) -> Vec<String> {
+    contract.functions()
+        .map(|f| format_signature(&f.name, f.inputs.iter().map(|p| &p.kind)))
+        .collect()
+}
+
+
+//decodifico l'input di una transazione verso un contratto con ABI registrato, con i nomi dei parametri
+pub fn decode_call(contract: &Contract, input: &[u8]) -> Option<DecodedCall> {
+    let selector = input.get(..4)?;
+    let function = contract.functions().find(|f| f.short_signature() == selector)?;
+    let tokens = function.decode_input(&input[4..]).ok()?;
+    
+    let mut args = Map::new();
+    for (i, (param, token)) in function.inputs.iter().zip(tokens).enumerate() {
+        let name = if param.name.is_empty() { format!("arg{}", i) } else { param.name.clone() };
+        args.insert(name, token_to_json(token));
+    }
+    
+    Some(DecodedCall {
+        name: function.name.clone(),
+        signature: format_signature(&function.name, function.inputs.iter().map(|p| &p.kind)),
+        args: Value::Object(args),
+    })
+}
+
+
+//valore ABI in JSON: gli interi diventano stringhe decimali (un uint256 non sta in un numero JSON),
+//byte e indirizzi stringhe esadecimali, array e tuple array JSON
+pub fn token_to_json(token: Token) -> Value {
+    match token {
+        Token::Address(address) => Value::String(bytes_to_hex(address.as_bytes())),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => Value::String(bytes_to_hex(&bytes)),
+        Token::Uint(value) => Value::String(value.to_string()),
+        //gli int sono in complemento a due su 256 bit
+        Token::Int(value) => {
+            if value.bit(255) {
+                Value::String(format!("-{}", (!value).overflowing_add(1.into()).0))
+            } else {
+                Value::String(value.to_string())
+            }
+        }
+        Token::Bool(value) => Value::Bool(value),
+        Token::String(value) => Value::String(value),
+        Token::FixedArray(tokens) | Token::Array(tokens) | Token::Tuple(tokens) => {
+            Value::Array(tokens.into_iter().map(token_to_json).collect())
+        }
+    }
+}
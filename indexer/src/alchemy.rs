@@ -1,217 +1,138 @@
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
-use futures_util::{StreamExt, SinkExt}; 
-use serde_json::json;
-use std::error::Error;
-use std::pin::Pin;
-use std::future::Future;
-use reqwest::Client;
-use crate::models::{JRPCRequest, JRPCResponse, Block};
-
-
-pub struct AlchemyWebSocket {
-    url: String,
-}
-
-
-impl AlchemyWebSocket {
-    //costruttore
-    pub fn new(api_key: String) -> Self {
-        // uso wss x aprire un canale di comunicazione PERMANENTE 
-        let url = format!("wss://eth-sepolia.g.alchemy.com/v2/{}", api_key);
-        Self { url }
-    }
-
-
-    async fn connect_and_listen<F>(
-    &self,
-    callback: &mut F
-) -> Result<(), Box<dyn Error + Send + Sync>>
-where F: FnMut(String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + 'static
-{
-    //apro una connessione webSocket con alchemy 
-    let (ws_stream, _) = connect_async(&self.url).await?; 
-    println!("connected to webSocket");
-    
-    //suddivisione dei canali di scrittura e lettura 
-    let (mut write, mut read) = ws_stream.split();
-
-    //richiesta di iscrivermi al feed che annuncia quando ci sono nuovi blocchi
-    let iscrizione = crate::models::JRPCRequest {
-        jsonrpc: "2.0".to_string(),
-        id: 1,
-        method: "eth_subscribe".to_string(),
-        params: vec![serde_json::json!("newHeads")], 
-    };
-
-    let iscrizione_str = serde_json::to_string(&iscrizione)?;
-    write.send(Message::Text(iscrizione_str)).await?;
-    
-    while let Some(mesg) = read.next().await {
-        if mesg.is_err() {
-            println!("network error: {:?}", mesg.err());
-            continue; 
-        }
-
-        let messaggio = mesg.unwrap();
-
-        // se messaggio è di tipo testo
-        if messaggio.is_text() {
-            let testo = messaggio.to_string();
-            
-            //converto la stringa in json
-            let json_testo = serde_json::from_str::<serde_json::Value>(&testo);
-            
-            if json_testo.is_err() {
-                continue;
-            }
-
-            let json_value = json_testo.unwrap();
-
-            let params = json_value.get("params");
-            if params.is_some() {
-                
-                let result = params.unwrap().get("result");
-                
-                if result.is_some() {
-                    let number = result.unwrap().get("number");
-                    if number.is_some() {
-                        //ottengo il numero del blocco
-                        let numero_hex = number.unwrap().as_str().unwrap();
-                        callback(numero_hex.to_string()).await; //chiamo la callback
-                    }
-                }
-            }
-        }
-
-        else if messaggio.is_ping() {
-            let dati = messaggio.into_data();
-            write.send(Message::Pong(dati)).await?;
-        }
-
-        else if messaggio.is_close() {
-            println!("server ha chiuso la connessione");
-            break;
-        }
-    }
-    
-    println!("disconnected");
-    Ok(())
-}
-
-
-    //funzione che serve ad INIZIARE per poi mettersi in ascolto
-    pub async fn subscribe_new_blocks<F>(
-        &self, 
-        mut callback: F 
-    ) -> Result<(), Box<dyn Error + Send + Sync>> where  
-        F: FnMut(String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + 'static
-    {
-        
-        loop {
-            println!("connecting to webSocket...");
-
-            let res = self.connect_and_listen(&mut callback).await;
-            
-            match res {
-                Ok(()) => { println!("webSocket connection closed gracefully. Reconnecting..."); }
-                Err(e) => {
-                    eprintln!("webSocket error: {}", e);
-                    println!("reconnecting in 5 sec...");
-
-                    //metto in pausa 5 sec prima di ritentare di nuovo di connettere
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                }
-            }
-        }
-    }
-}
-
-
-//----------------------------------------------------------------------------------------------------------------
-// per fare richieste "una tantum" --> serve per recuperare il passato
-pub struct AlchemyClient {
-    http_client: Client,
-    url: String,
-}
-
-impl AlchemyClient {
-    pub fn new(api_key: String) -> Self {
-        let http_client = Client::new();
-        let url = format!("https://eth-sepolia.g.alchemy.com/v2/{}", api_key);
-        
-        Self { http_client, url }
-    }
-    
-   
-    // per ottienere il numero dell'ultimo blocco
-    pub async fn get_latest_block_number(&self) -> Result<i64, Box<dyn Error + Send + Sync>> {
-   
-    let request = JRPCRequest {
-        jsonrpc: "2.0".to_string(),
-        method: "eth_blockNumber".to_string(),  
-        params: vec![],  
-        id: 1,
-    };
-    
-    //invio la richiesta HTTP POST
-    let response = self.http_client
-        .post(&self.url)
-        .json(&request)
-        .send()
-        .await?;
-    
-    let result: JRPCResponse<String> = response.json().await?;
-    
-    // Gestisci l'Option
-    let block_hex = result.result
-        .ok_or("No result in response")?;
-    
-    //trasformo esadecimale
-    let block_number = crate::utils::hex_to_i64(&block_hex)?;
-    Ok(block_number)
-}
-    
-    
-   // mi restituisce quel blocco
-pub async fn get_block(&self, block_number: i64) -> Result<Block, Box<dyn Error + Send + Sync>> {
-    let block_hex = format!("0x{:x}", block_number);
-    
-    let request = JRPCRequest {
-        jsonrpc: "2.0".to_string(),
-        method: "eth_getBlockByNumber".to_string(),
-        params: vec![
-            json!(block_hex),  
-            json!(true)
-        ],
-        id: 1,
-    };
-    
-    //faccio la richiesta
-    let response = self.http_client
-        .post(&self.url)
-        .json(&request)
-        .send()
-        .await?;
-    
-    //ottengo la risposta
-    let response_text = response.text().await?;
-
-    //prendo la stringa e la converto nel formato json
-    let result: JRPCResponse<Block> = serde_json::from_str(&response_text)
-        .map_err(|e| {
-            eprintln!("Failed to parse response for block {}: {}", block_number, e);
-            eprintln!("Response body: {}", response_text);
-            e
-        })?;
-    
-
-    if let Some(error) = result.error {
-        return Err(format!("RPC error: {:?}", error).into());
-    }
-    
-    //infine accedo al risultato vero e proprio ovvero il blocco
-    result.result.ok_or_else(|| "Block not found or null result".into())
-}
-    
-    
-}
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use futures_util::{StreamExt, SinkExt}; 
+use crate::error::IndexerError;
+use std::pin::Pin;
+use std::future::Future;
+use tracing::{error, info, warn};
+
+
+pub struct AlchemyWebSocket {
+    url: String,
+}
+
+
+//url di Alchemy (http e wss) per una rete, usati quando non viene configurato un endpoint esplicito
+pub fn urls(network: &str, api_key: &str) -> (String, String) {
+    (
+        format!("https://{}.g.alchemy.com/v2/{}", network, api_key),
+        format!("wss://{}.g.alchemy.com/v2/{}", network, api_key),
+    )
+}
+
+
+impl AlchemyWebSocket {
+    //costruttore: funziona con qualsiasi endpoint wss che supporta eth_subscribe
+    pub fn new(url: String) -> Self {
+        // uso wss x aprire un canale di comunicazione PERMANENTE 
+        Self { url }
+    }
+
+
+    async fn connect_and_listen<F>(
+    &self,
+    callback: &mut F
+) -> Result<(), IndexerError>
+where F: FnMut(String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + 'static
+{
+    //apro una connessione webSocket con alchemy 
+    let (ws_stream, _) = connect_async(&self.url).await?; 
+    info!(kind = "ws", "connected to WebSocket");
+    
+    //suddivisione dei canali di scrittura e lettura 
+    let (mut write, mut read) = ws_stream.split();
+
+    //richiesta di iscrivermi al feed che annuncia quando ci sono nuovi blocchi
+    let iscrizione = crate::models::JRPCRequest {
+        jsonrpc: "2.0".to_string(),
+        id: 1,
+        method: "eth_subscribe".to_string(),
+        params: vec![serde_json::json!("newHeads")], 
+    };
+
+    let iscrizione_str = serde_json::to_string(&iscrizione)?;
+    write.send(Message::Text(iscrizione_str)).await?;
+    
+    while let Some(mesg) = read.next().await {
+        if mesg.is_err() {
+            warn!(kind = "ws", error = ?mesg.err(), "WebSocket network error");
+            continue; 
+        }
+
+        let messaggio = mesg.unwrap();
+
+        // se messaggio è di tipo testo
+        if messaggio.is_text() {
+            let testo = messaggio.to_string();
+            
+            //converto la stringa in json
+            let json_testo = serde_json::from_str::<serde_json::Value>(&testo);
+            
+            if json_testo.is_err() {
+                continue;
+            }
+
+            let json_value = json_testo.unwrap();
+
+            if let Some(params) = json_value.get("params") {
+                
+                if let Some(result) = params.get("result") {
+                    
+                    if let Some(number) = result.get("number").and_then(|n| n.as_str()) {
+                        //ottengo il numero del blocco
+                        callback(number.to_string()).await; //chiamo la callback
+                    }
+                }
+            }
+        }
+
+        else if messaggio.is_ping() {
+            let dati = messaggio.into_data();
+            write.send(Message::Pong(dati)).await?;
+        }
+
+        else if messaggio.is_close() {
+            info!(kind = "ws", "server closed the WebSocket connection");
+            break;
+        }
+    }
+    
+    info!(kind = "ws", "WebSocket disconnected");
+    Ok(())
+}
+
+
+    //funzione che serve ad INIZIARE per poi mettersi in ascolto
+    pub async fn subscribe_new_blocks<F>(
+        &self, 
+        mut callback: F 
+    ) -> Result<(), IndexerError> where  
+        F: FnMut(String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + 'static
+    {
+        
+        //solo l'host: nell'url di Alchemy/Infura c'e' la chiave API, che non deve finire nei log
+        let host = reqwest::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_default();
+        
+        loop {
+            info!(kind = "ws", %host, "connecting to WebSocket");
+
+            let res = self.connect_and_listen(&mut callback).await;
+            
+            match res {
+                Ok(()) => {
+                    info!(kind = "ws", "WebSocket connection closed, reconnecting");
+                    crate::metrics::WS_RECONNECTS.inc();
+                }
+                Err(e) => {
+                    error!(kind = "ws", error = %e, "WebSocket error, reconnecting in 5s");
+
+                    //metto in pausa 5 sec prima di ritentare di nuovo di connettere
+                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    crate::metrics::WS_RECONNECTS.inc();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::response::Html;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::error::IndexerError;
+use crate::provider::EthProvider;
+use crate::{db, graphql, metrics, proxy, queries};
+use tracing::{error, info, warn};
+
+//elementi per pagina se non indicato, e massimo accettato
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+
+//stato condiviso dagli handler: db in sola lettura e provider per la testa della catena
+pub struct ApiState<P: EthProvider> {
+    pub provider: Arc<P>,
+    pub db: Arc<PgPool>,
+    pub chain_id: i64,
+    pub schema: graphql::IndexSchema,
+}
+
+
+//API REST in sola lettura sui dati indicizzati, ascolta su `addr` finche' il processo vive
+pub async fn serve<P: EthProvider>(
+    provider: Arc<P>,
+    db: Arc<PgPool>,
+    chain_id: i64,
+    addr: String
+) -> Result<(), IndexerError> {
+    
+    let schema = graphql::schema(Arc::clone(&db), chain_id);
+    let state = Arc::new(ApiState { provider, db, chain_id, schema });
+    
+    let app = Router::new()
+        .route("/status", get(status::<P>))
+        .route("/blocks", get(blocks::<P>))
+        .route("/blocks/:id", get(block::<P>))
+        .route("/tx/:hash", get(transaction::<P>))
+        .route("/address/:address/txs", get(address_transactions::<P>))
+        .route("/graphql", post(graphql_query::<P>))
+        .route("/graphql/ui", get(graphql_ui))
+        .route("/rpc", post(proxy::handle::<P>))
+        .route("/metrics", get(metrics_text))
+        .with_state(state);
+    
+    let listener = tokio::net::TcpListener::bind(&addr).await
+        .map_err(|e| IndexerError::Config(format!("cannot listen on {}: {}", addr, e)))?;
+    info!(%addr, "REST API listening");
+    axum::serve(listener, app).await?;
+    
+    Ok(())
+}
+
+
+//errore restituito al client come {"error": "..."}
+pub struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn not_found(what: &str) -> Self {
+        ApiError(StatusCode::NOT_FOUND, format!("{} not found", what))
+    }
+    
+    fn bad_request(message: impl Into<String>) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, message.into())
+    }
+}
+
+//i dettagli non vanno mostrati al client, li stampo con la variante come kind.
+//se il problema e' il nodo rispondo 502, se il db non e' raggiungibile 503, il resto e' un errore interno
+impl From<IndexerError> for ApiError {
+    fn from(e: IndexerError) -> Self {
+        error!(kind = e.kind(), error = %e, "API request failed");
+        match &e {
+            IndexerError::Transport(_) | IndexerError::Rpc { .. } => ApiError(StatusCode::BAD_GATEWAY, "provider unavailable".to_string()),
+            IndexerError::Db(_) if e.is_transient() => ApiError(StatusCode::SERVICE_UNAVAILABLE, "database unavailable".to_string()),
+            _ => ApiError(StatusCode::INTERNAL_SERVER_ERROR, "internal error".to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult = Result<Json<Value>, ApiError>;
+
+
+//ultimo blocco indicizzato rispetto alla testa della catena
+async fn status<P: EthProvider>(State(state): State<Arc<ApiState<P>>>) -> ApiResult {
+    let last_indexed = db::get_last_indexed_block(&state.db, state.chain_id).await?;
+    
+    //se il nodo non risponde restituisco comunque lo stato del db
+    let head = match state.provider.get_latest_block_number().await {
+        Ok(head) => Some(head),
+        Err(e) => {
+            warn!(kind = "transport", error = %e, "API status: cannot read chain head");
+            None
+        }
+    };
+    
+    Ok(Json(json!({
+        "chain_id": state.chain_id,
+        "last_block_indexed": last_indexed,
+        "head": head,
+        "lag": head.map(|h| h - last_indexed),
+    })))
+}
+
+
+#[derive(Deserialize)]
+struct BlocksQuery {
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+//blocchi da `from` a `to` in ordine crescente. next_cursor e' il primo blocco della pagina dopo
+async fn blocks<P: EthProvider>(State(state): State<Arc<ApiState<P>>>, Query(query): Query<BlocksQuery>) -> ApiResult {
+    let limit = page_size(query.limit)?;
+    let from = match &query.cursor {
+        Some(cursor) => cursor.parse::<i64>().map_err(|_| ApiError::bad_request("invalid cursor"))?,
+        None => query.from.unwrap_or(0),
+    };
+    let to = query.to.unwrap_or(i64::MAX);
+    
+    let mut items = queries::get_blocks(&state.db, state.chain_id, from, to, limit + 1).await?;
+    let next_cursor = if items.len() as i64 > limit {
+        items.pop().map(|b| b.number.to_string())
+    } else {
+        None
+    };
+    
+    Ok(Json(json!({ "items": items, "next_cursor": next_cursor })))
+}
+
+
+//blocco per numero (decimale o 0x) o per hash, con gli hash delle sue transazioni
+async fn block<P: EthProvider>(State(state): State<Arc<ApiState<P>>>, Path(id): Path<String>) -> ApiResult {
+    let found = if id.len() == 66 && id.starts_with("0x") {
+        queries::get_block_by_hash(&state.db, state.chain_id, &id).await?
+    } else {
+        let number = match id.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => id.parse::<i64>(),
+        }
+        .map_err(|_| ApiError::bad_request("expected a block number or a block hash"))?;
+        queries::get_block_by_number(&state.db, state.chain_id, number).await?
+    };
+    
+    let block = found.ok_or_else(|| ApiError::not_found("block"))?;
+    let transactions: Vec<String> = queries::get_block_transactions(&state.db, state.chain_id, block.number)
+        .await?
+        .into_iter()
+        .map(|tx| tx.hash)
+        .collect();
+    
+    let mut body = json!(block);
+    body["transactions"] = json!(transactions);
+    Ok(Json(body))
+}
+
+
+//transazione con la sua ricevuta e i log
+async fn transaction<P: EthProvider>(State(state): State<Arc<ApiState<P>>>, Path(hash): Path<String>) -> ApiResult {
+    let tx = queries::get_transaction(&state.db, state.chain_id, &hash)
+        .await?
+        .ok_or_else(|| ApiError::not_found("transaction"))?;
+    let receipt = queries::get_receipt(&state.db, state.chain_id, &hash).await?;
+    let logs = queries::get_transaction_logs(&state.db, state.chain_id, &hash).await?;
+    
+    Ok(Json(json!({ "transaction": tx, "receipt": receipt, "logs": logs })))
+}
+
+
+#[derive(Deserialize)]
+struct PageQuery {
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+//transazioni di un indirizzo, dalla piu' recente. Il cursore e' "blocco:indice" dell'ultima restituita
+async fn address_transactions<P: EthProvider>(
+    State(state): State<Arc<ApiState<P>>>,
+    Path(address): Path<String>,
+    Query(query): Query<PageQuery>
+) -> ApiResult {
+    let limit = page_size(query.limit)?;
+    let before = query.cursor.as_deref().map(parse_tx_cursor).transpose()?;
+    
+    let mut items = queries::get_address_transactions(&state.db, state.chain_id, &address, before, limit + 1).await?;
+    let next_cursor = if items.len() as i64 > limit {
+        items.pop();
+        items.last().map(|tx| format!("{}:{}", tx.block_number, tx.transaction_index))
+    } else {
+        None
+    };
+    
+    Ok(Json(json!({ "items": items, "next_cursor": next_cursor })))
+}
+
+
+//query GraphQL nel formato standard {"query", "variables", "operationName"}
+async fn graphql_query<P: EthProvider>(
+    State(state): State<Arc<ApiState<P>>>,
+    Json(request): Json<async_graphql::Request>
+) -> Json<async_graphql::Response> {
+    Json(state.schema.execute(request).await)
+}
+
+//GraphiQL per provare le query dal browser
+async fn graphql_ui() -> Html<String> {
+    Html(async_graphql::http::GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+
+//metriche nel formato testo di Prometheus
+async fn metrics_text() -> String {
+    metrics::render()
+}
+
+
+fn page_size(limit: Option<i64>) -> Result<i64, ApiError> {
+    match limit {
+        None => Ok(DEFAULT_PAGE_SIZE),
+        Some(l) if (1..=MAX_PAGE_SIZE).contains(&l) => Ok(l),
+        Some(_) => Err(ApiError::bad_request(format!("limit must be between 1 and {}", MAX_PAGE_SIZE))),
+    }
+}
+
+fn parse_tx_cursor(cursor: &str) -> Result<(i64, i32), ApiError> {
+    cursor.split_once(':')
+        .and_then(|(block, index)| Some((block.parse().ok()?, index.parse().ok()?)))
+        .ok_or_else(|| ApiError::bad_request("invalid cursor"))
+}
@@ -0,0 +1,93 @@
+use sqlx::PgPool;
+use crate::error::IndexerError;
+use crate::provider::EthProvider;
+use crate::{db, pipeline};
+use crate::pipeline::WriteMode;
+use tracing::{error, info};
+
+
+//controllo di consistenza della tabella blocks: numeri mancanti e collegamenti parent_hash -> hash
+//rotti tra blocchi consecutivi. Con repair = true i blocchi vengono riscaricati con index_block
+pub async fn run<P: EthProvider>(
+    provider: &P,
+    db_pool: &PgPool,
+    chain_id: i64,
+    repair: bool
+) -> Result<(), IndexerError> {
+    
+    let gaps = db::find_gaps(db_pool, chain_id).await?;
+    let broken = db::find_broken_links(db_pool, chain_id).await?;
+    
+    let missing: i64 = gaps.iter().map(|(from, to)| to - from + 1).sum();
+    info!(chain_id, gaps = gaps.len(), missing, broken_links = broken.len(), "audit report");
+    for (from, to) in &gaps {
+        info!(from, to, "gap");
+    }
+    for number in &broken {
+        info!(block = number, "broken parent link");
+    }
+    
+    if !repair {
+        return Ok(());
+    }
+    
+    let mut failed = 0;
+    
+    for (from, to) in gaps {
+        for block_num in from..=to {
+            if let Err(e) = pipeline::index_block(provider, db_pool, chain_id, block_num, WriteMode::Insert).await {
+                error!(block = block_num, error = %e, "error repairing block");
+                failed += 1;
+            }
+        }
+    }
+    
+    for number in broken {
+        if let Err(e) = repair_link(provider, db_pool, chain_id, number).await {
+            error!(block = number, error = %e, "error repairing parent link");
+            failed += 1;
+        }
+    }
+    
+    info!(still_failing = failed, "audit repair done");
+    //uscita con errore, cosi' script e cron distinguono una riparazione non riuscita
+    if failed > 0 {
+        return Err(IndexerError::Consistency(format!("{} blocks could not be repaired", failed)));
+    }
+    Ok(())
+}
+
+
+//un collegamento rotto vuol dire che uno dei due blocchi (o anche qualcuno prima) non e' canonico:
+//scendo finche' l'hash salvato coincide con quello del provider, poi riscarico dal basso verso l'alto
+async fn repair_link<P: EthProvider>(
+    provider: &P,
+    db_pool: &PgPool,
+    chain_id: i64,
+    block_number: i64
+) -> Result<(), IndexerError> {
+    
+    let mut wrong = Vec::new();
+    
+    if db::get_block_hash(db_pool, chain_id, block_number).await? != Some(provider.get_block_hash(block_number).await?) {
+        wrong.push(block_number);
+    }
+    
+    let mut num = block_number - 1;
+    while let Some(stored) = db::get_block_hash(db_pool, chain_id, num).await? {
+        if stored == provider.get_block_hash(num).await? {
+            break;
+        }
+        wrong.push(num);
+        num -= 1;
+    }
+    
+    //Overwrite come reindex: il blocco viene riscaricato prima, poi cancellato e riscritto nella
+    //stessa transazione, quindi una riparazione interrotta non lascia buchi
+    for num in wrong.into_iter().rev() {
+        info!(block = num, "re-indexing non canonical block");
+        pipeline::index_block(provider, db_pool, chain_id, num, WriteMode::Overwrite).await?;
+    }
+    
+    Ok(())
+}
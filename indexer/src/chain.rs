@@ -0,0 +1,51 @@
+use crate::error::IndexerError;
+use crate::provider::EthProvider;
+use crate::quantity::Quantity;
+
+//reti conosciute: nome, chain id e sottodominio di Alchemy
+const NETWORKS: &[(&str, i64, &str)] = &[
+    ("mainnet", 1, "eth-mainnet"),
+    ("sepolia", 11155111, "eth-sepolia"),
+    ("holesky", 17000, "eth-holesky"),
+];
+
+
+//chain id configurato: un chain id esplicito per qualsiasi chain EVM, oppure il nome di una
+//rete conosciuta. Senza nessuno dei due resto su Sepolia come prima
+pub fn configured_chain_id(chain_id: Option<i64>, network: Option<&str>) -> Result<i64, String> {
+    if let Some(id) = chain_id {
+        return Ok(id);
+    }
+    
+    let network = network.unwrap_or("sepolia");
+    NETWORKS.iter()
+        .find(|(name, _, _)| *name == network)
+        .map(|(_, id, _)| *id)
+        .ok_or_else(|| format!("unknown network {}, set chain.chain_id (CHAIN_ID) instead", network))
+}
+
+
+//sottodominio di Alchemy per il chain id, None se la rete non e' tra quelle conosciute
+pub fn alchemy_network(chain_id: i64) -> Option<&'static str> {
+    NETWORKS.iter()
+        .find(|(_, id, _)| *id == chain_id)
+        .map(|(_, _, slug)| *slug)
+}
+
+
+//controllo che l'endpoint sia davvero sulla chain configurata, cosi' non mischio dati di reti diverse
+pub async fn verify_chain_id<P: EthProvider>(
+    provider: &P,
+    expected: i64
+) -> Result<(), IndexerError> {
+    let chain_hex: String = provider.request("eth_chainId", vec![])
+        .await?
+        .ok_or_else(|| IndexerError::Consistency("null result for eth_chainId".to_string()))?;
+    
+    let actual = Quantity::from_hex(&chain_hex)?.to_int::<i64>()?;
+    if actual != expected {
+        return Err(IndexerError::Config(format!("endpoint is on chain {} but chain {} is configured", actual, expected)));
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,372 @@
+use serde::Deserialize;
+use std::env;
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::{alchemy, chain};
+use crate::retry::RetryPolicy;
+
+//file letto se non viene passato --config (o CONFIG_FILE), se non esiste si usano solo le variabili d'ambiente
+pub const DEFAULT_CONFIG_FILE: &str = "indexer.toml";
+
+//massimo batch JSON-RPC accettato in configurazione, oltre quasi tutti i provider rifiutano la richiesta
+const MAX_RPC_BATCH_SIZE: usize = 1000;
+
+//pipeline attive, lette dal salvataggio dei blocchi senza doverle passare a ogni funzione
+static PIPELINES: OnceLock<PipelinesConfig> = OnceLock::new();
+
+
+//configurazione dell'indexer: file TOML, poi le variabili d'ambiente (anche da .env) sopra il file,
+//poi le opzioni della riga di comando. Ogni sezione ha dei default, tranne db e provider
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub provider: ProviderConfig,
+    pub chain: ChainConfig,
+    pub sync: SyncConfig,
+    pub pipelines: PipelinesConfig,
+    pub api: ApiConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    pub rpc_url: Option<String>,
+    pub ws_url: Option<String>,
+    pub alchemy_api_key: Option<String>,
+    pub poll_interval_secs: u64,
+    pub batch_size: usize,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChainConfig {
+    pub chain_id: Option<i64>,
+    pub network: Option<String>,
+    pub start_block: Option<i64>,
+    pub confirmations: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    pub concurrency: usize,
+    pub retry_attempts: u32,
+    pub retry_base_delay_ms: u64,
+    pub retry_max_delay_ms: u64,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelinesConfig {
+    pub tokens: bool,
+    pub contracts: bool,
+    pub traces: bool,
+    pub decoded_events: bool,
+    pub decoded_calls: bool,
+    pub abi_dir: Option<String>,
+    pub signatures_file: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub addr: Option<String>,
+}
+
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self { rpc_url: None, ws_url: None, alchemy_api_key: None, poll_interval_secs: 12, batch_size: 100 }
+    }
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self { concurrency: 10, retry_attempts: 5, retry_base_delay_ms: 500, retry_max_delay_ms: 30_000 }
+    }
+}
+
+//le tracce restano spente: richiedono le API debug, che molti provider non offrono
+impl Default for PipelinesConfig {
+    fn default() -> Self {
+        Self {
+            tokens: true,
+            contracts: true,
+            traces: false,
+            decoded_events: true,
+            decoded_calls: true,
+            abi_dir: None,
+            signatures_file: None,
+        }
+    }
+}
+
+
+impl Config {
+    //leggo il file (se c'e') e ci applico sopra le variabili d'ambiente.
+    //un file indicato esplicitamente deve esistere, quello di default e' facoltativo
+    pub fn load(path: Option<&str>) -> Result<Self, Vec<String>> {
+        let mut config = match path {
+            Some(path) => Self::from_file(Path::new(path)).map_err(|e| vec![e])?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_FILE)).map_err(|e| vec![e])?,
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read config file {}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("invalid config file {}: {}", path.display(), e))
+    }
+
+    //stessi nomi delle variabili usate finora, cosi' i .env esistenti continuano a funzionare
+    fn apply_env(&mut self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        env_string("DB_HOST", &mut self.database.host);
+        env_value("DB_PORT", &mut self.database.port, &mut errors);
+        env_string("DB_USER", &mut self.database.user);
+        env_string("DB_PASSWORD", &mut self.database.password);
+        env_string("DB_NAME", &mut self.database.name);
+
+        env_string("RPC_URL", &mut self.provider.rpc_url);
+        env_string("WS_URL", &mut self.provider.ws_url);
+        env_string("ALCHEMY_API_KEY", &mut self.provider.alchemy_api_key);
+        env_parse("POLL_INTERVAL_SECS", &mut self.provider.poll_interval_secs, &mut errors);
+        env_parse("RPC_BATCH_SIZE", &mut self.provider.batch_size, &mut errors);
+
+        env_value("CHAIN_ID", &mut self.chain.chain_id, &mut errors);
+        env_string("NETWORK", &mut self.chain.network);
+        env_value("START_BLOCK", &mut self.chain.start_block, &mut errors);
+        env_parse("CONFIRMATIONS", &mut self.chain.confirmations, &mut errors);
+
+        env_parse("CATCHUP_CONCURRENCY", &mut self.sync.concurrency, &mut errors);
+        env_parse("RETRY_ATTEMPTS", &mut self.sync.retry_attempts, &mut errors);
+        env_parse("RETRY_BASE_DELAY_MS", &mut self.sync.retry_base_delay_ms, &mut errors);
+        env_parse("RETRY_MAX_DELAY_MS", &mut self.sync.retry_max_delay_ms, &mut errors);
+
+        env_flag("TOKENS_ENABLED", &mut self.pipelines.tokens, &mut errors);
+        env_flag("CONTRACTS_ENABLED", &mut self.pipelines.contracts, &mut errors);
+        env_flag("TRACES_ENABLED", &mut self.pipelines.traces, &mut errors);
+        env_flag("DECODED_EVENTS_ENABLED", &mut self.pipelines.decoded_events, &mut errors);
+        env_flag("DECODED_CALLS_ENABLED", &mut self.pipelines.decoded_calls, &mut errors);
+        env_string("ABI_DIR", &mut self.pipelines.abi_dir);
+        env_string("SIGNATURES_FILE", &mut self.pipelines.signatures_file);
+
+        env_string("API_ADDR", &mut self.api.addr);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    //controllo tutto all'avvio e restituisco tutti i problemi insieme
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        for (value, key, var) in [
+            (&self.database.host, "database.host", "DB_HOST"),
+            (&self.database.user, "database.user", "DB_USER"),
+            (&self.database.name, "database.name", "DB_NAME"),
+        ] {
+            if value.as_deref().is_none_or(str::is_empty) {
+                errors.push(format!("{} is missing (set it in the config file or with {})", key, var));
+            }
+        }
+
+        match self.chain_id() {
+            Ok(chain_id) => {
+                if let Err(e) = self.provider.urls(chain_id) {
+                    errors.push(e);
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+
+        if let Some(ws_url) = &self.provider.ws_url {
+            if !ws_url.starts_with("ws://") && !ws_url.starts_with("wss://") {
+                errors.push(format!("provider.ws_url must start with ws:// or wss://, got {}", ws_url));
+            }
+        }
+        if self.provider.poll_interval_secs == 0 {
+            errors.push("provider.poll_interval_secs must be at least 1".to_string());
+        }
+        if self.provider.batch_size == 0 || self.provider.batch_size > MAX_RPC_BATCH_SIZE {
+            errors.push(format!("provider.batch_size must be between 1 and {}, got {}", MAX_RPC_BATCH_SIZE, self.provider.batch_size));
+        }
+
+        if self.chain.start_block.is_some_and(|b| b < 0) {
+            errors.push("chain.start_block cannot be negative".to_string());
+        }
+        if self.chain.confirmations < 0 {
+            errors.push("chain.confirmations cannot be negative".to_string());
+        }
+
+        if self.sync.concurrency == 0 {
+            errors.push("sync.concurrency must be at least 1".to_string());
+        }
+        if self.sync.retry_attempts == 0 {
+            errors.push("sync.retry_attempts must be at least 1".to_string());
+        }
+        if self.sync.retry_base_delay_ms > self.sync.retry_max_delay_ms {
+            errors.push("sync.retry_base_delay_ms cannot be greater than sync.retry_max_delay_ms".to_string());
+        }
+
+        if let Some(dir) = &self.pipelines.abi_dir {
+            if !Path::new(dir).is_dir() {
+                errors.push(format!("pipelines.abi_dir {} is not a directory", dir));
+            }
+        }
+        if let Some(file) = &self.pipelines.signatures_file {
+            if !Path::new(file).is_file() {
+                errors.push(format!("pipelines.signatures_file {} does not exist", file));
+            }
+        }
+        if self.pipelines.traces && !self.pipelines.contracts {
+            errors.push("pipelines.traces only finds contract creations, enable pipelines.contracts too".to_string());
+        }
+
+        if let Some(addr) = &self.api.addr {
+            if addr.to_socket_addrs().map(|mut a| a.next().is_none()).unwrap_or(true) {
+                errors.push(format!("api.addr {} is not a valid host:port", addr));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    //chain id: chain.chain_id per qualsiasi chain EVM, oppure chain.network con il nome di una rete conosciuta
+    pub fn chain_id(&self) -> Result<i64, String> {
+        chain::configured_chain_id(self.chain.chain_id, self.chain.network.as_deref())
+    }
+
+    //opzioni di connessione a Postgres: la password passa cosi' com'e', senza finire dentro un url
+    pub fn connect_options(&self) -> sqlx::postgres::PgConnectOptions {
+        let db = &self.database;
+        let mut options = sqlx::postgres::PgConnectOptions::new();
+
+        //DB_HOST puo' contenere anche la porta (host:porta), come nell'url usato prima
+        if let Some(host) = &db.host {
+            match host.rsplit_once(':').and_then(|(h, p)| p.parse::<u16>().ok().map(|p| (h, p))) {
+                Some((h, p)) => options = options.host(h).port(p),
+                None => options = options.host(host),
+            }
+        }
+        if let Some(port) = db.port {
+            options = options.port(port);
+        }
+        if let Some(user) = &db.user {
+            options = options.username(user);
+        }
+        if let Some(password) = &db.password {
+            options = options.password(password);
+        }
+        if let Some(name) = &db.name {
+            options = options.database(name);
+        }
+
+        options
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempts: self.sync.retry_attempts.max(1),
+            base_delay: std::time::Duration::from_millis(self.sync.retry_base_delay_ms),
+            max_delay: std::time::Duration::from_millis(self.sync.retry_max_delay_ms),
+        }
+    }
+}
+
+
+impl ProviderConfig {
+    //url del provider e, se c'e', quello per eth_subscribe. rpc_url puo' essere http(s), ws(s) o il path
+    //del socket IPC di un nodo locale; senza rpc_url uso Alchemy sulla rete configurata
+    pub fn urls(&self, chain_id: i64) -> Result<(String, Option<String>), String> {
+        if let Some(url) = &self.rpc_url {
+            //senza schema http/ws e' il path di un socket IPC, che c'e' solo su unix
+            if cfg!(not(unix)) && !["http://", "https://", "ws://", "wss://"].iter().any(|scheme| url.starts_with(scheme)) {
+                return Err(format!("provider.rpc_url {} is an IPC socket, only supported on unix: use an http(s) or ws(s) URL", url));
+            }
+            return Ok((url.clone(), self.ws_url.clone()));
+        }
+
+        let api_key = self.alchemy_api_key.as_deref()
+            .ok_or("either provider.rpc_url (RPC_URL) or provider.alchemy_api_key (ALCHEMY_API_KEY) must be set")?;
+        let network = chain::alchemy_network(chain_id)
+            .ok_or_else(|| format!("no Alchemy endpoint known for chain {}, set provider.rpc_url", chain_id))?;
+        let (http, ws) = alchemy::urls(network, api_key);
+        Ok((http, Some(self.ws_url.clone().unwrap_or(ws))))
+    }
+}
+
+
+//registro le pipeline attive per tutto il processo, da chiamare una volta all'avvio
+pub fn set_pipelines(pipelines: PipelinesConfig) {
+    let _ = PIPELINES.set(pipelines);
+}
+
+//pipeline attive; prima di set_pipelines valgono i default
+pub fn pipelines() -> &'static PipelinesConfig {
+    PIPELINES.get_or_init(PipelinesConfig::default)
+}
+
+
+//variabile d'ambiente non vuota
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+fn env_string(name: &str, target: &mut Option<String>) {
+    if let Some(v) = env_var(name) {
+        *target = Some(v);
+    }
+}
+
+fn env_parse<T: FromStr>(name: &str, target: &mut T, errors: &mut Vec<String>) {
+    if let Some(v) = env_var(name) {
+        match v.parse() {
+            Ok(parsed) => *target = parsed,
+            Err(_) => errors.push(format!("{} has an invalid value: {}", name, v)),
+        }
+    }
+}
+
+fn env_value<T: FromStr>(name: &str, target: &mut Option<T>, errors: &mut Vec<String>) {
+    if let Some(v) = env_var(name) {
+        match v.parse() {
+            Ok(parsed) => *target = Some(parsed),
+            Err(_) => errors.push(format!("{} has an invalid value: {}", name, v)),
+        }
+    }
+}
+
+fn env_flag(name: &str, target: &mut bool, errors: &mut Vec<String>) {
+    match env_var(name).as_deref() {
+        None => {}
+        Some("true" | "1") => *target = true,
+        Some("false" | "0") => *target = false,
+        Some(v) => errors.push(format!("{} must be true or false, got {}", name, v)),
+    }
+}
@@ -0,0 +1,89 @@
+use crate::error::IndexerError;
+use crate::models::{Block, CallFrame, Receipt, TxTrace};
+use crate::utils::{bytes_to_hex, hex_to_bytes, keccak256};
+
+
+//contratto creato in un blocco: da una transazione con `to` nullo oppure da una CREATE/CREATE2
+//interna trovata nelle tracce
+pub struct Deployment {
+    pub address: String,
+    pub deployer: String,
+    pub transaction_hash: String,
+    pub creation_type: &'static str,
+    //bytecode runtime, riempito dopo con eth_getCode
+    pub bytecode: String,
+}
+
+
+//cerco i contratti creati nel blocco. Le creazioni dirette arrivano dalle ricevute
+//(contractAddress), quelle interne dalle tracce se sono state scaricate
+pub fn find_deployments(block: &Block, receipts: &[Receipt], traces: &[TxTrace]) -> Vec<Deployment> {
+    let mut deployments = Vec::new();
+    
+    for (tx, receipt) in block.transactions.iter().zip(receipts) {
+        //status 0x0: la creazione e' andata in revert, il contratto non esiste
+        if tx.to.is_some() || receipt.status.is_some_and(|s| s.is_zero()) {
+            continue;
+        }
+        if let Some(address) = &receipt.contract_address {
+            deployments.push(Deployment {
+                address: address.to_lowercase(),
+                deployer: tx.from.to_lowercase(),
+                transaction_hash: tx.hash.clone(),
+                creation_type: "tx",
+                bytecode: String::new(),
+            });
+        }
+    }
+    
+    for (i, trace) in traces.iter().enumerate() {
+        //alcuni nodi non mettono txHash, in quel caso le tracce sono nell'ordine delle transazioni
+        let tx_hash = match (&trace.tx_hash, block.transactions.get(i)) {
+            (Some(hash), _) => hash.clone(),
+            (None, Some(tx)) => tx.hash.clone(),
+            (None, None) => continue,
+        };
+        //se la transazione e' andata in revert non resta niente di quello che ha creato
+        if trace.result.error.is_some() {
+            continue;
+        }
+        //il frame principale e' gia' coperto dalla ricevuta, guardo solo le chiamate interne
+        collect_internal_creates(&trace.result.calls, &tx_hash, &mut deployments);
+    }
+    
+    deployments
+}
+
+
+fn collect_internal_creates(calls: &[CallFrame], tx_hash: &str, deployments: &mut Vec<Deployment>) {
+    for frame in calls {
+        //se una chiamata fallisce anche tutto quello che ha creato viene annullato
+        if frame.error.is_some() {
+            continue;
+        }
+        
+        let creation_type = match frame.call_type.as_str() {
+            "CREATE" => Some("create"),
+            "CREATE2" => Some("create2"),
+            _ => None,
+        };
+        
+        if let (Some(creation_type), Some(address)) = (creation_type, &frame.to) {
+            deployments.push(Deployment {
+                address: address.to_lowercase(),
+                deployer: frame.from.to_lowercase(),
+                transaction_hash: tx_hash.to_string(),
+                creation_type,
+                bytecode: String::new(),
+            });
+        }
+        
+        collect_internal_creates(&frame.calls, tx_hash, deployments);
+    }
+}
+
+
+//keccak256 del bytecode (come il codeHash dell'account), per raggruppare i deploy identici
+pub fn bytecode_hash(bytecode: &str) -> Result<String, IndexerError> {
+    Ok(bytes_to_hex(&keccak256(&hex_to_bytes(bytecode)?)))
+}
@@ -1,69 +1,1004 @@
-use sqlx::{PgPool, Transaction, Postgres, Row};
-use crate::models::Block;
-use crate::utils::hex_to_i64;
-use std::error::Error;
-
-//metodo per ottenere l'ultimo blocco 
-pub async fn get_last_indexed_block(pool: &PgPool) -> Result<i64, Box<dyn Error + Send + Sync>> {
-    let row = sqlx::query("SELECT last_block_indexed FROM indexer_state WHERE id = 1")
-        .fetch_one(pool)
-        .await?;
-    
-    let last_block: i64 = row.get(0);
-    Ok(last_block)
-}
-
-//metodo per fare UPDATE sull'ultimo blocco salvato sul db
-pub async fn update_last_indexed_block(
-    pool: &PgPool, 
-    block_number: i64
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    sqlx::query(
-        "UPDATE indexer_state 
-         SET last_block_indexed = $1, last_update = NOW() 
-         WHERE id = 1"
-    )
-    .bind(block_number)
-    .execute(pool)
-    .await?;
-    
-    Ok(())
-}
-
-//metodo per salvare blocco
-pub async fn save_block(
-    db_transazione: &mut Transaction<'_, Postgres>,
-    block: &Block
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    
-    
-    //trasformo da esadecimali
-    let block_number = hex_to_i64(&block.number)?;
-    let timestamp = hex_to_i64(&block.timestamp)?;
-    let gas_used = hex_to_i64(&block.gas_used)?;
-    let gas_limit = hex_to_i64(&block.gas_limit)?;
-    let size = hex_to_i64(&block.size)?;
-    let tx_count = block.transactions.len() as i32;
-    
-    sqlx::query(
-        "INSERT INTO blocks 
-         (number, hash, parent_hash, timestamp, miner, gas_used, gas_limit, transactions_count, size)
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-         ON CONFLICT (number) DO NOTHING"
-    )
-    .bind(block_number)
-    .bind(&block.hash)
-    .bind(&block.parent_hash)
-    .bind(timestamp)
-    .bind(&block.miner)
-    .bind(gas_used)
-    .bind(gas_limit)
-    .bind(tx_count)
-    .bind(size)
-    .execute(&mut **db_transazione)
-    .await?;
-    
-    Ok(())
-}
-
-
+use sqlx::{PgPool, Transaction, Postgres, Row};
+use crate::models::{Block, Transaction as Tx, Receipt, Log};
+use crate::tokens::{self, Erc20Event};
+use crate::contracts::{self, Deployment};
+use crate::{abi, signatures};
+use crate::error::IndexerError;
+use std::collections::HashMap;
+use tracing::warn;
+
+//metodo per ottenere l'ultimo blocco 
+pub async fn get_last_indexed_block(pool: &PgPool, chain_id: i64) -> Result<i64, IndexerError> {
+    let row = sqlx::query("SELECT last_block_indexed FROM indexer_state WHERE chain_id = $1")
+        .bind(chain_id)
+        .fetch_one(pool)
+        .await?;
+    
+    let last_block: i64 = row.get(0);
+    Ok(last_block)
+}
+
+//metodo per creare la riga di stato della chain al primo avvio, non tocca una riga gia' esistente.
+//restituisce true se la riga e' stata creata
+pub async fn init_indexer_state(
+    pool: &PgPool,
+    chain_id: i64,
+    last_block_indexed: i64
+) -> Result<bool, IndexerError> {
+    let inserted = sqlx::query(
+        "INSERT INTO indexer_state (chain_id, last_block_indexed) 
+         VALUES ($1, $2)
+         ON CONFLICT (chain_id) DO NOTHING"
+    )
+    .bind(chain_id)
+    .bind(last_block_indexed)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    
+    Ok(inserted > 0)
+}
+
+//metodo per fare UPDATE sull'ultimo blocco salvato sul db, nella stessa transazione del blocco
+pub async fn update_last_indexed_block(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    block_number: i64
+) -> Result<(), IndexerError> {
+    //GREATEST per sicurezza: lo stato non deve mai tornare indietro se non con rollback_to
+    sqlx::query(
+        "UPDATE indexer_state 
+         SET last_block_indexed = GREATEST(last_block_indexed, $2), last_update = NOW() 
+         WHERE chain_id = $1"
+    )
+    .bind(chain_id)
+    .bind(block_number)
+    .execute(&mut **db_transazione)
+    .await?;
+    
+    Ok(())
+}
+
+//metodo per salvare blocco
+pub async fn save_block(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    block: &Block
+) -> Result<(), IndexerError> {
+    
+    
+    //quantita' gia' validate dal parsing, qui solo la conversione controllata per le colonne BIGINT
+    let block_number = block.number.to_int::<i64>()?;
+    let timestamp = block.timestamp.to_int::<i64>()?;
+    let gas_used = block.gas_used.to_int::<i64>()?;
+    let gas_limit = block.gas_limit.to_int::<i64>()?;
+    let size = block.size.to_int::<i64>()?;
+    let tx_count = block.transactions.len() as i32;
+    let difficulty = block.difficulty.map(|q| q.to_decimal());
+    let total_difficulty = block.total_difficulty.map(|q| q.to_decimal());
+    let base_fee_per_gas = block.base_fee_per_gas.map(|q| q.to_decimal());
+    let blob_gas_used = block.blob_gas_used.map(|q| q.to_int::<i64>()).transpose()?;
+    let excess_blob_gas = block.excess_blob_gas.map(|q| q.to_int::<i64>()).transpose()?;
+    //le liste vanno come testo JSON con $n::JSONB
+    let uncles = serde_json::json!(block.uncles).to_string();
+    let withdrawals = block.withdrawals.as_ref().map(|w| serde_json::json!(w).to_string());
+    
+    sqlx::query(
+        "INSERT INTO blocks 
+         (chain_id, number, hash, parent_hash, timestamp, miner, gas_used, gas_limit, transactions_count, size,
+          difficulty, total_difficulty, state_root, transactions_root, receipts_root, logs_bloom, extra_data,
+          mix_hash, nonce, base_fee_per_gas, withdrawals_root, blob_gas_used, excess_blob_gas,
+          parent_beacon_block_root, requests_hash, sha3_uncles, uncles, withdrawals)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11::NUMERIC, $12::NUMERIC, $13, $14, $15, $16, $17,
+          $18, $19, $20::NUMERIC, $21, $22, $23, $24, $25, $26, $27::JSONB, $28::JSONB)
+         ON CONFLICT (chain_id, number) DO NOTHING"
+    )
+    .bind(chain_id)
+    .bind(block_number)
+    .bind(&block.hash)
+    .bind(&block.parent_hash)
+    .bind(timestamp)
+    .bind(&block.miner)
+    .bind(gas_used)
+    .bind(gas_limit)
+    .bind(tx_count)
+    .bind(size)
+    .bind(difficulty)
+    .bind(total_difficulty)
+    .bind(&block.state_root)
+    .bind(&block.transactions_root)
+    .bind(&block.receipts_root)
+    .bind(&block.logs_bloom)
+    .bind(&block.extra_data)
+    .bind(&block.mix_hash)
+    .bind(&block.nonce)
+    .bind(base_fee_per_gas)
+    .bind(&block.withdrawals_root)
+    .bind(blob_gas_used)
+    .bind(excess_blob_gas)
+    .bind(&block.parent_beacon_block_root)
+    .bind(&block.requests_hash)
+    .bind(&block.sha3_uncles)
+    .bind(uncles)
+    .bind(withdrawals)
+    .execute(&mut **db_transazione)
+    .await?;
+    
+    //salvo le transazioni nella stessa transazione del db del blocco
+    for tx in &block.transactions {
+        save_transaction(db_transazione, chain_id, tx).await?;
+    }
+    
+    Ok(())
+}
+
+//metodo per salvare una transazione
+pub async fn save_transaction(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    tx: &Tx
+) -> Result<(), IndexerError> {
+    
+    //i valori in wei possono superare i64, li salvo come NUMERIC passando la stringa decimale
+    let block_number = tx.block_number.to_int::<i64>()?;
+    let tx_index = tx.transaction_index.to_int::<i32>()?;
+    let value = tx.value.to_decimal();
+    let nonce = tx.nonce.to_int::<i64>()?;
+    let gas = tx.gas.to_int::<i64>()?;
+    let gas_price = tx.gas_price.map(|q| q.to_decimal());
+    let max_fee = tx.max_fee_per_gas.map(|q| q.to_decimal());
+    let max_priority_fee = tx.max_priority_fee_per_gas.map(|q| q.to_decimal());
+    let tx_type = tx.tx_type.map(|t| t.to_int::<i16>()).transpose()?;
+    //chain id firmato nella transazione, assente per le legacy pre EIP-155
+    let tx_chain_id = tx.chain_id.map(|c| c.to_int::<i64>()).transpose()?;
+    let y_parity = tx.y_parity.map(|p| p.to_int::<i16>()).transpose()?;
+    
+    sqlx::query(
+        "INSERT INTO transactions 
+         (chain_id, hash, block_number, transaction_index, from_address, to_address, value, nonce, gas,
+          gas_price, max_fee_per_gas, max_priority_fee_per_gas, input, tx_type, tx_chain_id,
+          v, r, s, y_parity, access_list, max_fee_per_blob_gas, blob_versioned_hashes, authorization_list)
+         VALUES ($1, $2, $3, $4, $5, $6, $7::NUMERIC, $8, $9, $10::NUMERIC, $11::NUMERIC, $12::NUMERIC, $13, $14, $15,
+          $16::NUMERIC, $17::NUMERIC, $18::NUMERIC, $19, $20::JSONB, $21::NUMERIC, $22::JSONB, $23::JSONB)
+         ON CONFLICT (chain_id, hash) DO NOTHING"
+    )
+    .bind(chain_id)
+    .bind(&tx.hash)
+    .bind(block_number)
+    .bind(tx_index)
+    .bind(&tx.from)
+    .bind(&tx.to)
+    .bind(value)
+    .bind(nonce)
+    .bind(gas)
+    .bind(gas_price)
+    .bind(max_fee)
+    .bind(max_priority_fee)
+    .bind(&tx.input)
+    .bind(tx_type)
+    .bind(tx_chain_id)
+    .bind(tx.v.map(|q| q.to_decimal()))
+    .bind(tx.r.map(|q| q.to_decimal()))
+    .bind(tx.s.map(|q| q.to_decimal()))
+    .bind(y_parity)
+    .bind(tx.access_list.as_ref().map(|l| l.to_string()))
+    .bind(tx.max_fee_per_blob_gas.map(|q| q.to_decimal()))
+    .bind(tx.blob_versioned_hashes.as_ref().map(|h| h.to_string()))
+    .bind(tx.authorization_list.as_ref().map(|l| l.to_string()))
+    .execute(&mut **db_transazione)
+    .await?;
+    
+    Ok(())
+}
+
+//metodo per salvare le ricevute di un blocco con i relativi log
+pub async fn save_receipts(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    receipts: &[Receipt]
+) -> Result<(), IndexerError> {
+    
+    for receipt in receipts {
+        let block_number = receipt.block_number.to_int::<i64>()?;
+        let status = receipt.status.map(|s| s.to_int::<i16>()).transpose()?;
+        let gas_used = receipt.gas_used.to_int::<i64>()?;
+        let cumulative_gas_used = receipt.cumulative_gas_used.to_int::<i64>()?;
+        let effective_gas_price = receipt.effective_gas_price.map(|q| q.to_decimal());
+        let blob_gas_used = receipt.blob_gas_used.map(|q| q.to_int::<i64>()).transpose()?;
+        
+        sqlx::query(
+            "INSERT INTO receipts 
+             (chain_id, transaction_hash, block_number, status, gas_used, cumulative_gas_used, effective_gas_price, contract_address,
+              logs_bloom, root, blob_gas_used, blob_gas_price)
+             VALUES ($1, $2, $3, $4, $5, $6, $7::NUMERIC, $8, $9, $10, $11, $12::NUMERIC)
+             ON CONFLICT (chain_id, transaction_hash) DO NOTHING"
+        )
+        .bind(chain_id)
+        .bind(&receipt.transaction_hash)
+        .bind(block_number)
+        .bind(status)
+        .bind(gas_used)
+        .bind(cumulative_gas_used)
+        .bind(effective_gas_price)
+        .bind(&receipt.contract_address)
+        .bind(&receipt.logs_bloom)
+        .bind(&receipt.root)
+        .bind(blob_gas_used)
+        .bind(receipt.blob_gas_price.map(|q| q.to_decimal()))
+        .execute(&mut **db_transazione)
+        .await?;
+        
+        for log in &receipt.logs {
+            save_log(db_transazione, chain_id, log).await?;
+        }
+    }
+    
+    Ok(())
+}
+
+//metodo per salvare un log, i topic mancanti restano NULL
+pub async fn save_log(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    log: &Log
+) -> Result<(), IndexerError> {
+    
+    let block_number = log.block_number.to_int::<i64>()?;
+    let log_index = log.log_index.to_int::<i32>()?;
+    
+    sqlx::query(
+        "INSERT INTO logs 
+         (chain_id, block_number, log_index, transaction_hash, address, topic0, topic1, topic2, topic3, data)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+         ON CONFLICT (chain_id, block_number, log_index) DO NOTHING"
+    )
+    .bind(chain_id)
+    .bind(block_number)
+    .bind(log_index)
+    .bind(&log.transaction_hash)
+    .bind(&log.address)
+    .bind(log.topics.first())
+    .bind(log.topics.get(1))
+    .bind(log.topics.get(2))
+    .bind(log.topics.get(3))
+    .bind(&log.data)
+    .execute(&mut **db_transazione)
+    .await?;
+    
+    Ok(())
+}
+
+//metodo per salvare i Transfer e gli Approval ERC-20 trovati nei log delle ricevute
+pub async fn save_erc20_events(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    receipts: &[Receipt]
+) -> Result<(), IndexerError> {
+    
+    for log in receipts.iter().flat_map(|r| &r.logs) {
+        //chiunque puo' emettere un log con questi topic: se il data non e' valido salto il log, non il blocco
+        let event = match tokens::decode_erc20(log) {
+            Ok(Some(event)) => event,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!(kind = "decode", tx = %log.transaction_hash, log_index = %log.log_index, error = %e, "skipping malformed ERC-20 log");
+                continue;
+            }
+        };
+        
+        let (table, first_col, second_col, first, second, value) = match event {
+            Erc20Event::Transfer { from, to, value } => ("erc20_transfers", "from_address", "to_address", from, to, value),
+            Erc20Event::Approval { owner, spender, value } => ("erc20_approvals", "owner_address", "spender_address", owner, spender, value),
+        };
+        
+        sqlx::query(&format!(
+            "INSERT INTO {} 
+             (chain_id, block_number, log_index, transaction_hash, token, {}, {}, value)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8::NUMERIC)
+             ON CONFLICT (chain_id, block_number, log_index) DO NOTHING",
+            table, first_col, second_col
+        ))
+        .bind(chain_id)
+        .bind(log.block_number.to_int::<i64>()?)
+        .bind(log.log_index.to_int::<i32>()?)
+        .bind(&log.transaction_hash)
+        .bind(log.address.to_lowercase())
+        .bind(first)
+        .bind(second)
+        .bind(value)
+        .execute(&mut **db_transazione)
+        .await?;
+    }
+    
+    Ok(())
+}
+
+//metodo per salvare i trasferimenti ERC-721 / ERC-1155 e aggiornare nft_owners.
+//il saldo viene toccato solo se il trasferimento e' nuovo, cosi' salvare due volte lo stesso blocco non lo altera
+pub async fn save_nft_transfers(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    receipts: &[Receipt]
+) -> Result<(), IndexerError> {
+    
+    for log in receipts.iter().flat_map(|r| &r.logs) {
+        let contract = log.address.to_lowercase();
+        
+        let transfers = match tokens::decode_nft(log) {
+            Ok(transfers) => transfers,
+            Err(e) => {
+                warn!(kind = "decode", tx = %log.transaction_hash, log_index = %log.log_index, error = %e, "skipping malformed NFT transfer log");
+                continue;
+            }
+        };
+        
+        for transfer in transfers {
+            let inserted = sqlx::query(
+                "INSERT INTO nft_transfers 
+                 (chain_id, block_number, log_index, batch_index, transaction_hash, contract, standard,
+                  operator, from_address, to_address, token_id, amount)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11::NUMERIC, $12::NUMERIC)
+                 ON CONFLICT (chain_id, block_number, log_index, batch_index) DO NOTHING"
+            )
+            .bind(chain_id)
+            .bind(log.block_number.to_int::<i64>()?)
+            .bind(log.log_index.to_int::<i32>()?)
+            .bind(transfer.batch_index)
+            .bind(&log.transaction_hash)
+            .bind(&contract)
+            .bind(transfer.standard)
+            .bind(&transfer.operator)
+            .bind(&transfer.from)
+            .bind(&transfer.to)
+            .bind(&transfer.token_id)
+            .bind(&transfer.amount)
+            .execute(&mut **db_transazione)
+            .await?
+            .rows_affected();
+            
+            if inserted > 0 {
+                apply_nft_delta(db_transazione, chain_id, &contract, &transfer.token_id, &transfer.from, &format!("-{}", transfer.amount)).await?;
+                apply_nft_delta(db_transazione, chain_id, &contract, &transfer.token_id, &transfer.to, &transfer.amount).await?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+//metodo per salvare i contratti creati nel blocco, con l'hash del bytecode per raggrupparli
+pub async fn save_contracts(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    block_number: i64,
+    deployments: &[Deployment]
+) -> Result<(), IndexerError> {
+    
+    for deployment in deployments {
+        sqlx::query(
+            "INSERT INTO contracts 
+             (chain_id, address, block_number, transaction_hash, deployer, creation_type, bytecode, bytecode_hash)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+             ON CONFLICT (chain_id, address, block_number) DO NOTHING"
+        )
+        .bind(chain_id)
+        .bind(&deployment.address)
+        .bind(block_number)
+        .bind(&deployment.transaction_hash)
+        .bind(&deployment.deployer)
+        .bind(deployment.creation_type)
+        .bind(&deployment.bytecode)
+        .bind(contracts::bytecode_hash(&deployment.bytecode)?)
+        .execute(&mut **db_transazione)
+        .await?;
+    }
+    
+    Ok(())
+}
+
+//metodo per registrare (o sostituire) l'ABI di un contratto
+pub async fn save_abi(pool: &PgPool, chain_id: i64, address: &str, abi: &serde_json::Value) -> Result<(), IndexerError> {
+    sqlx::query(
+        "INSERT INTO abis (chain_id, address, abi) VALUES ($1, $2, $3::JSONB)
+         ON CONFLICT (chain_id, address) DO UPDATE SET abi = EXCLUDED.abi, updated_at = now()"
+    )
+    .bind(chain_id)
+    .bind(address.to_lowercase())
+    .bind(abi.to_string())
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+//metodo per leggere gli ABI registrati per alcuni indirizzi, ABI gia' pronti per decodificare.
+//un ABI non valido inserito a mano viene saltato, non deve bloccare l'indicizzazione
+async fn get_abis(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    addresses: &[String]
+) -> Result<HashMap<String, ethabi::Contract>, IndexerError> {
+    let rows = sqlx::query("SELECT address, abi::TEXT AS abi FROM abis WHERE chain_id = $1 AND address = ANY($2)")
+        .bind(chain_id)
+        .bind(addresses)
+        .fetch_all(&mut **db_transazione)
+        .await?;
+    
+    let mut contracts = HashMap::new();
+    for row in rows {
+        let address: String = row.try_get("address")?;
+        let text: String = row.try_get("abi")?;
+        
+        match serde_json::from_str(&text).map_err(|e| e.into()).and_then(|v| abi::parse(&v)) {
+            Ok(contract) => { contracts.insert(address, contract); }
+            Err(e) => warn!(kind = "decode", %address, error = %e, "invalid ABI in abis table"),
+        }
+    }
+    
+    Ok(contracts)
+}
+
+//metodo per salvare i log dei contratti con un ABI registrato, decodificati in decoded_events
+pub async fn save_decoded_events(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    receipts: &[Receipt]
+) -> Result<(), IndexerError> {
+    
+    let mut addresses: Vec<String> = receipts.iter()
+        .flat_map(|r| &r.logs)
+        .map(|log| log.address.to_lowercase())
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+    
+    if addresses.is_empty() {
+        return Ok(());
+    }
+    
+    let contracts = get_abis(db_transazione, chain_id, &addresses).await?;
+    if contracts.is_empty() {
+        return Ok(());
+    }
+    
+    for log in receipts.iter().flat_map(|r| &r.logs) {
+        let contract = log.address.to_lowercase();
+        let event = match contracts.get(&contract) {
+            Some(abi) => abi::decode_log(abi, log)?,
+            None => None,
+        };
+        let event = match event {
+            Some(event) => event,
+            None => continue,
+        };
+        
+        sqlx::query(
+            "INSERT INTO decoded_events 
+             (chain_id, block_number, log_index, transaction_hash, contract, event_name, signature, params)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8::JSONB)
+             ON CONFLICT (chain_id, block_number, log_index) DO NOTHING"
+        )
+        .bind(chain_id)
+        .bind(log.block_number.to_int::<i64>()?)
+        .bind(log.log_index.to_int::<i32>()?)
+        .bind(&log.transaction_hash)
+        .bind(&contract)
+        .bind(&event.name)
+        .bind(&event.signature)
+        .bind(event.params.to_string())
+        .execute(&mut **db_transazione)
+        .await?;
+    }
+    
+    Ok(())
+}
+
+//metodo per aggiungere firme di funzione al registro dei selettori
+pub async fn save_signatures(pool: &PgPool, signatures: &[String], source: &str) -> Result<(), IndexerError> {
+    let selectors: Vec<String> = signatures.iter().map(|s| signatures::selector(s)).collect();
+    
+    sqlx::query(
+        "INSERT INTO function_signatures (selector, signature, source)
+         SELECT s.selector, s.signature, $3 FROM UNNEST($1::TEXT[], $2::TEXT[]) AS s(selector, signature)
+         ON CONFLICT (selector, signature) DO NOTHING"
+    )
+    .bind(&selectors)
+    .bind(signatures)
+    .bind(source)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+//metodo per annotare le transazioni del blocco con il metodo chiamato e i suoi argomenti.
+//prima provo l'ABI registrato per il destinatario (ha i nomi dei parametri), poi le firme note
+//per il selettore, iniziando da quelle che vengono da un ABI
+pub async fn save_decoded_calls(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    block: &Block
+) -> Result<(), IndexerError> {
+    
+    //solo chiamate a contratti con almeno il selettore, non i deploy e i semplici invii di ETH
+    let mut calls = Vec::new();
+    for tx in &block.transactions {
+        if let Some(to) = &tx.to {
+            let input = crate::utils::hex_to_bytes(&tx.input)?;
+            if input.len() >= 4 {
+                calls.push((tx, to.to_lowercase(), input));
+            }
+        }
+    }
+    
+    if calls.is_empty() {
+        return Ok(());
+    }
+    
+    let mut addresses: Vec<String> = calls.iter().map(|(_, to, _)| to.clone()).collect();
+    addresses.sort();
+    addresses.dedup();
+    let contracts = get_abis(db_transazione, chain_id, &addresses).await?;
+    
+    let selectors: Vec<String> = calls.iter().map(|(_, _, input)| crate::utils::bytes_to_hex(&input[..4])).collect();
+    let rows = sqlx::query(
+        "SELECT selector, signature FROM function_signatures WHERE selector = ANY($1)
+         ORDER BY (source = 'abi') DESC, signature"
+    )
+    .bind(&selectors)
+    .fetch_all(&mut **db_transazione)
+    .await?;
+    
+    let mut known: HashMap<String, Vec<String>> = HashMap::new();
+    for row in rows {
+        known.entry(row.try_get("selector")?).or_default().push(row.try_get("signature")?);
+    }
+    
+    for ((tx, to, input), selector) in calls.iter().zip(&selectors) {
+        let decoded = contracts.get(to)
+            .and_then(|contract| abi::decode_call(contract, input))
+            .or_else(|| {
+                known.get(selector)?
+                    .iter()
+                    .find_map(|signature| signatures::decode_call(signature, input))
+            });
+        
+        let call = match decoded {
+            Some(call) => call,
+            None => continue,
+        };
+        
+        sqlx::query(
+            "UPDATE transactions SET method_name = $3, method_signature = $4, method_args = $5::JSONB
+             WHERE chain_id = $1 AND hash = $2"
+        )
+        .bind(chain_id)
+        .bind(&tx.hash)
+        .bind(&call.name)
+        .bind(&call.signature)
+        .bind(call.args.to_string())
+        .execute(&mut **db_transazione)
+        .await?;
+    }
+    
+    Ok(())
+}
+
+//metodo per annullare l'effetto su nft_owners dei trasferimenti tra from_block e to_block,
+//chiamato prima di cancellarli (reorg o riparazione). I delta commutano, li annullo comunque
+//dall'ultimo al primo cosi' i saldi intermedi sono quelli che la catena ha davvero avuto
+async fn revert_nft_transfers(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    from_block: i64,
+    to_block: i64
+) -> Result<(), IndexerError> {
+    
+    let rows = sqlx::query(
+        "SELECT contract, token_id::TEXT, from_address, to_address, amount::TEXT FROM nft_transfers 
+         WHERE chain_id = $1 AND block_number BETWEEN $2 AND $3
+         ORDER BY block_number DESC, log_index DESC, batch_index DESC"
+    )
+    .bind(chain_id)
+    .bind(from_block)
+    .bind(to_block)
+    .fetch_all(&mut **db_transazione)
+    .await?;
+    
+    for row in rows {
+        let contract: String = row.get(0);
+        let token_id: String = row.get(1);
+        let from: String = row.get(2);
+        let to: String = row.get(3);
+        let amount: String = row.get(4);
+        
+        apply_nft_delta(db_transazione, chain_id, &contract, &token_id, &to, &format!("-{}", amount)).await?;
+        apply_nft_delta(db_transazione, chain_id, &contract, &token_id, &from, &amount).await?;
+    }
+    
+    Ok(())
+}
+
+//metodo per sommare delta (stringa decimale, anche negativa) al saldo di un proprietario.
+//l'indirizzo zero (mint e burn) non viene tracciato e le righe a zero vengono tolte.
+//un saldo puo' andare sotto zero quando i blocchi arrivano fuori ordine (backfill, reindex, failed_blocks):
+//la riga resta finche' non arriva il trasferimento che la compensa, cosi' i delta si sommano in qualsiasi ordine.
+//il proprietario corrente e' chi ha balance > 0
+async fn apply_nft_delta(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    contract: &str,
+    token_id: &str,
+    owner: &str,
+    delta: &str
+) -> Result<(), IndexerError> {
+    
+    if owner == ZERO_ADDRESS {
+        return Ok(());
+    }
+    
+    sqlx::query(
+        "INSERT INTO nft_owners (chain_id, contract, token_id, owner, balance)
+         VALUES ($1, $2, $3::NUMERIC, $4, $5::NUMERIC)
+         ON CONFLICT (chain_id, contract, token_id, owner) 
+         DO UPDATE SET balance = nft_owners.balance + EXCLUDED.balance"
+    )
+    .bind(chain_id)
+    .bind(contract)
+    .bind(token_id)
+    .bind(owner)
+    .bind(delta)
+    .execute(&mut **db_transazione)
+    .await?;
+    
+    sqlx::query(
+        "DELETE FROM nft_owners 
+         WHERE chain_id = $1 AND contract = $2 AND token_id = $3::NUMERIC AND owner = $4 AND balance = 0"
+    )
+    .bind(chain_id)
+    .bind(contract)
+    .bind(token_id)
+    .bind(owner)
+    .execute(&mut **db_transazione)
+    .await?;
+    
+    Ok(())
+}
+
+//metodo per ottenere l'hash salvato di un blocco, None se il blocco non e' sul db
+pub async fn get_block_hash(pool: &PgPool, chain_id: i64, block_number: i64) -> Result<Option<String>, IndexerError> {
+    let row = sqlx::query("SELECT hash FROM blocks WHERE chain_id = $1 AND number = $2")
+        .bind(chain_id)
+        .bind(block_number)
+        .fetch_optional(pool)
+        .await?;
+    
+    Ok(row.map(|r| r.get(0)))
+}
+
+//metodo per ottenere il blocco salvato piu' alto sotto block_number, con il suo hash
+pub async fn get_block_below(pool: &PgPool, chain_id: i64, block_number: i64) -> Result<Option<(i64, String)>, IndexerError> {
+    let row = sqlx::query("SELECT number, hash FROM blocks WHERE chain_id = $1 AND number < $2 ORDER BY number DESC LIMIT 1")
+        .bind(chain_id)
+        .bind(block_number)
+        .fetch_optional(pool)
+        .await?;
+    
+    Ok(row.map(|r| (r.get(0), r.get(1))))
+}
+
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+//tabelle con righe legate a un blocco (colonna block_number), da svuotare prima di blocks
+const BLOCK_TABLES: &[&str] = &["decoded_events", "contracts", "nft_transfers", "erc20_transfers", "erc20_approvals", "logs", "receipts", "transactions"];
+
+//metodo per cancellare tutto quello che sta sopra l'antenato comune dopo un reorg,
+//le tabelle figlie vanno svuotate prima di blocks per via delle foreign key
+pub async fn rollback_to(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    ancestor: i64
+) -> Result<u64, IndexerError> {
+    
+    //prima di cancellare i trasferimenti NFT riporto indietro i saldi in nft_owners
+    revert_nft_transfers(db_transazione, chain_id, ancestor + 1, i64::MAX).await?;
+    
+    for table in BLOCK_TABLES {
+        sqlx::query(&format!("DELETE FROM {} WHERE chain_id = $1 AND block_number > $2", table))
+            .bind(chain_id)
+            .bind(ancestor)
+            .execute(&mut **db_transazione)
+            .await?;
+    }
+    
+    let deleted = sqlx::query("DELETE FROM blocks WHERE chain_id = $1 AND number > $2")
+        .bind(chain_id)
+        .bind(ancestor)
+        .execute(&mut **db_transazione)
+        .await?
+        .rows_affected();
+    
+    //riporto indietro lo stato cosi' il ramo canonico viene ri-indicizzato
+    sqlx::query(
+        "UPDATE indexer_state 
+         SET last_block_indexed = LEAST(last_block_indexed, $2), last_update = NOW() 
+         WHERE chain_id = $1"
+    )
+    .bind(chain_id)
+    .bind(ancestor)
+    .execute(&mut **db_transazione)
+    .await?;
+    
+    Ok(deleted)
+}
+
+//metodo per registrare un reorg nella tabella di audit
+pub async fn save_reorg(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    block_number: i64,
+    orphaned_hash: &str,
+    canonical_hash: &str,
+    common_ancestor: i64,
+    depth: i64
+) -> Result<(), IndexerError> {
+    sqlx::query(
+        "INSERT INTO reorgs 
+         (chain_id, block_number, orphaned_hash, canonical_hash, common_ancestor, depth)
+         VALUES ($1, $2, $3, $4, $5, $6)"
+    )
+    .bind(chain_id)
+    .bind(block_number)
+    .bind(orphaned_hash)
+    .bind(canonical_hash)
+    .bind(common_ancestor)
+    .bind(depth)
+    .execute(&mut **db_transazione)
+    .await?;
+    
+    Ok(())
+}
+
+//metodo per mettere un blocco in failed_blocks o aggiornare i tentativi se c'e' gia'.
+//il prossimo tentativo viene spostato sempre piu' avanti (30s, 1m, 2m... fino a 1h)
+pub async fn save_failed_block(
+    pool: &PgPool,
+    chain_id: i64,
+    block_number: i64,
+    error: &str,
+    attempts: i32
+) -> Result<(), IndexerError> {
+    sqlx::query(
+        "INSERT INTO failed_blocks (chain_id, block_number, error, attempts, next_retry_at)
+         VALUES ($1, $2, $3, $4, NOW() + INTERVAL '30 seconds')
+         ON CONFLICT (chain_id, block_number) DO UPDATE SET
+            error = EXCLUDED.error,
+            attempts = failed_blocks.attempts + EXCLUDED.attempts,
+            last_attempt_at = NOW(),
+            next_retry_at = NOW() + LEAST(30 * POWER(2, LEAST(failed_blocks.attempts, 10)), 3600) * INTERVAL '1 second'"
+    )
+    .bind(chain_id)
+    .bind(block_number)
+    .bind(error)
+    .bind(attempts)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+//metodo per ottenere i blocchi falliti da ritentare adesso
+pub async fn get_due_failed_blocks(
+    pool: &PgPool,
+    chain_id: i64,
+    limit: i64
+) -> Result<Vec<i64>, IndexerError> {
+    let rows = sqlx::query(
+        "SELECT block_number FROM failed_blocks 
+         WHERE chain_id = $1 AND next_retry_at <= NOW()
+         ORDER BY block_number
+         LIMIT $2"
+    )
+    .bind(chain_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(rows.iter().map(|r| r.get(0)).collect())
+}
+
+//metodo per togliere un blocco da failed_blocks, chiamato quando il blocco viene salvato
+pub async fn delete_failed_block(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    block_number: i64
+) -> Result<(), IndexerError> {
+    sqlx::query("DELETE FROM failed_blocks WHERE chain_id = $1 AND block_number = $2")
+        .bind(chain_id)
+        .bind(block_number)
+        .execute(&mut **db_transazione)
+        .await?;
+    
+    Ok(())
+}
+
+//metodo per contare i blocchi ancora in failed_blocks
+pub async fn count_failed_blocks(pool: &PgPool, chain_id: i64) -> Result<i64, IndexerError> {
+    let row = sqlx::query("SELECT COUNT(*) FROM failed_blocks WHERE chain_id = $1")
+        .bind(chain_id)
+        .fetch_one(pool)
+        .await?;
+    
+    Ok(row.get(0))
+}
+
+//metodo per cancellare un singolo blocco con tutte le sue righe, usato dalla riparazione e dal reindex
+pub async fn delete_block(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    block_number: i64
+) -> Result<(), IndexerError> {
+    
+    revert_nft_transfers(db_transazione, chain_id, block_number, block_number).await?;
+    
+    for table in BLOCK_TABLES {
+        sqlx::query(&format!("DELETE FROM {} WHERE chain_id = $1 AND block_number = $2", table))
+            .bind(chain_id)
+            .bind(block_number)
+            .execute(&mut **db_transazione)
+            .await?;
+    }
+    
+    sqlx::query("DELETE FROM blocks WHERE chain_id = $1 AND number = $2")
+        .bind(chain_id)
+        .bind(block_number)
+        .execute(&mut **db_transazione)
+        .await?;
+    
+    Ok(())
+}
+
+//metodo per trovare i buchi nella tabella blocks: intervalli (inizio, fine) di numeri mancanti
+//tra il primo blocco salvato e last_block_indexed
+pub async fn find_gaps(pool: &PgPool, chain_id: i64) -> Result<Vec<(i64, i64)>, IndexerError> {
+    let rows = sqlx::query(
+        "SELECT number + 1, next_number - 1 FROM (
+            SELECT number, LEAD(number) OVER (ORDER BY number) AS next_number
+            FROM blocks WHERE chain_id = $1
+         ) t
+         WHERE next_number > number + 1
+         UNION ALL
+         SELECT MAX(b.number) + 1, s.last_block_indexed
+         FROM blocks b JOIN indexer_state s ON s.chain_id = b.chain_id
+         WHERE b.chain_id = $1
+         GROUP BY s.last_block_indexed
+         HAVING MAX(b.number) < s.last_block_indexed
+         ORDER BY 1"
+    )
+    .bind(chain_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+//metodo per trovare i blocchi il cui parent_hash non coincide con l'hash del blocco precedente salvato
+pub async fn find_broken_links(pool: &PgPool, chain_id: i64) -> Result<Vec<i64>, IndexerError> {
+    let rows = sqlx::query(
+        "SELECT b.number FROM blocks b
+         JOIN blocks p ON p.chain_id = b.chain_id AND p.number = b.number - 1
+         WHERE b.chain_id = $1 AND b.parent_hash <> p.hash
+         ORDER BY b.number"
+    )
+    .bind(chain_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(rows.iter().map(|r| r.get(0)).collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CHAIN_ID: i64 = 31337;
+    const CONTRACT: &str = "0x00000000000000000000000000000000000000cc";
+    const A: &str = "0x000000000000000000000000000000000000000a";
+    const B: &str = "0x000000000000000000000000000000000000000b";
+    const C: &str = "0x000000000000000000000000000000000000000c";
+
+    //serve un Postgres: TEST_DATABASE_URL=postgres://... cargo test -- --ignored.
+    //tutto avviene in una transazione che non viene confermata, il db resta com'era
+    async fn test_transaction() -> Transaction<'static, Postgres> {
+        let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL not set");
+        let pool = PgPool::connect(&url).await.unwrap();
+        crate::migrations::run(&pool).await.unwrap();
+        pool.begin().await.unwrap()
+    }
+
+    async fn insert_block(db_transazione: &mut Transaction<'_, Postgres>, number: i64) {
+        sqlx::query(
+            "INSERT INTO blocks (chain_id, number, hash, parent_hash, timestamp, miner, gas_used, gas_limit, transactions_count, size)
+             VALUES ($1, $2, '0x', '0x', 0, '0x', 0, 0, 0, 0)"
+        )
+        .bind(CHAIN_ID)
+        .bind(number)
+        .execute(&mut **db_transazione)
+        .await
+        .unwrap();
+    }
+
+    //ricevuta con un solo Transfer ERC-721 del token 7
+    fn transfer_receipt(number: i64, from: &str, to: &str) -> Receipt {
+        let topic = |address: &str| format!("0x{:0>64}", address.trim_start_matches("0x"));
+        let tx_hash = format!("0x{:064x}", number);
+        serde_json::from_value(json!({
+            "transactionHash": tx_hash,
+            "blockNumber": format!("{:#x}", number),
+            "status": "0x1",
+            "gasUsed": "0x0",
+            "cumulativeGasUsed": "0x0",
+            "logs": [{
+                "address": CONTRACT,
+                "topics": [tokens::TRANSFER_TOPIC, topic(from), topic(to), format!("0x{:064x}", 7)],
+                "data": "0x",
+                "logIndex": "0x0",
+                "transactionHash": tx_hash,
+                "blockNumber": format!("{:#x}", number),
+            }],
+        }))
+        .unwrap()
+    }
+
+    async fn owners(db_transazione: &mut Transaction<'_, Postgres>) -> Vec<(String, String)> {
+        sqlx::query_as(
+            "SELECT owner, balance::TEXT FROM nft_owners WHERE chain_id = $1 AND contract = $2 ORDER BY owner"
+        )
+        .bind(CHAIN_ID)
+        .bind(CONTRACT)
+        .fetch_all(&mut **db_transazione)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs PostgreSQL in TEST_DATABASE_URL"]
+    async fn revert_multi_hop_nft_transfers() {
+        let mut db_transazione = test_transaction().await;
+
+        //A -> B nel blocco 1, B -> C nel blocco 2: alla fine il token e' di C
+        for (number, from, to) in [(1, A, B), (2, B, C)] {
+            insert_block(&mut db_transazione, number).await;
+            save_nft_transfers(&mut db_transazione, CHAIN_ID, &[transfer_receipt(number, from, to)]).await.unwrap();
+        }
+        apply_nft_delta(&mut db_transazione, CHAIN_ID, CONTRACT, "7", A, "1").await.unwrap();
+        assert_eq!(owners(&mut db_transazione).await, vec![(C.to_string(), "1".to_string())]);
+
+        revert_nft_transfers(&mut db_transazione, CHAIN_ID, 1, 2).await.unwrap();
+
+        assert_eq!(owners(&mut db_transazione).await, vec![(A.to_string(), "1".to_string())]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs PostgreSQL in TEST_DATABASE_URL"]
+    async fn nft_transfers_out_of_order_and_reindex() {
+        let mut db_transazione = test_transaction().await;
+
+        //mint a A nel blocco 0, A -> B nel blocco 1, B -> C nel blocco 2, salvati dal piu' recente
+        let transfers = [(0, ZERO_ADDRESS, A), (1, A, B), (2, B, C)];
+        for (number, from, to) in transfers.iter().rev() {
+            insert_block(&mut db_transazione, *number).await;
+            save_nft_transfers(&mut db_transazione, CHAIN_ID, &[transfer_receipt(*number, from, to)]).await.unwrap();
+        }
+        assert_eq!(owners(&mut db_transazione).await, vec![(C.to_string(), "1".to_string())]);
+
+        //reindex del blocco 1: cancellato e salvato di nuovo nella stessa transazione
+        delete_block(&mut db_transazione, CHAIN_ID, 1).await.unwrap();
+        insert_block(&mut db_transazione, 1).await;
+        save_nft_transfers(&mut db_transazione, CHAIN_ID, &[transfer_receipt(1, A, B)]).await.unwrap();
+
+        assert_eq!(owners(&mut db_transazione).await, vec![(C.to_string(), "1".to_string())]);
+    }
+}
@@ -0,0 +1,120 @@
+use serde_json::Value;
+
+//errori della pipeline di indicizzazione, divisi per origine: chi li riceve decide se ritentare,
+//mettere il blocco in failed_blocks o fermarsi guardando la variante e non il testo
+#[derive(Debug, thiserror::Error)]
+pub enum IndexerError {
+    //rete, timeout, connessione chiusa: la richiesta non ha avuto risposta
+    #[error("RPC transport error: {0}")]
+    Transport(String),
+
+    //il nodo ha risposto con un oggetto error JSON-RPC
+    #[error("RPC error {code}: {message}")]
+    Rpc { code: i64, message: String },
+
+    //risposta o dato in un formato che non mi aspetto (JSON, esadecimale, ABI)
+    #[error("decode error: {0}")]
+    Decode(String),
+
+    #[error("database error: {0}")]
+    Db(#[from] sqlx::Error),
+
+    //dati che non tornano tra provider e db: blocco non (ancora) disponibile, reorg troppo profondo...
+    #[error("consistency error: {0}")]
+    Consistency(String),
+
+    //reorg oltre MAX_REORG_DEPTH: non si ritenta e non si va avanti, serve un controllo a mano
+    #[error("deep reorg: {0}")]
+    DeepReorg(String),
+
+    //configurazione o file locali (ABI, firme) non validi, endpoint sulla chain sbagliata
+    #[error("configuration error: {0}")]
+    Config(String),
+}
+
+//codici JSON-RPC che dipendono dalla richiesta e non dal momento: ritentare non serve
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+
+impl IndexerError {
+    //oggetto error di una risposta JSON-RPC ({"code": ..., "message": ...})
+    pub fn from_rpc(error: &Value) -> Self {
+        IndexerError::Rpc {
+            code: error.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+            message: error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error").to_string(),
+        }
+    }
+
+    //vale la pena ritentare: errori di rete, limiti del provider, db non raggiungibile o in conflitto,
+    //dati non ancora allineati. Un formato sbagliato o un vincolo violato restano tali a ogni tentativo
+    pub fn is_transient(&self) -> bool {
+        match self {
+            IndexerError::Transport(_) => true,
+            IndexerError::Rpc { code, .. } => !matches!(*code, INVALID_REQUEST | METHOD_NOT_FOUND | INVALID_PARAMS),
+            IndexerError::Decode(_) => false,
+            IndexerError::Db(sqlx::Error::Database(e)) => {
+                //classe 23: vincoli di integrita', 42: errori di sintassi o schema
+                !e.code().is_some_and(|code| code.starts_with("23") || code.starts_with("42"))
+            }
+            IndexerError::Db(_) => true,
+            IndexerError::Consistency(_) => true,
+            IndexerError::DeepReorg(_) => false,
+            IndexerError::Config(_) => false,
+        }
+    }
+
+    //valore del campo `kind` nei log, su cui impostare gli allarmi
+    pub fn kind(&self) -> &'static str {
+        match self {
+            IndexerError::Transport(_) => "transport",
+            IndexerError::Rpc { .. } => "rpc",
+            IndexerError::Decode(_) => "decode",
+            IndexerError::Db(_) => "db",
+            IndexerError::Consistency(_) => "consistency",
+            IndexerError::DeepReorg(_) => "reorg",
+            IndexerError::Config(_) => "config",
+        }
+    }
+}
+
+
+//senza url: per Alchemy e Infura contiene la chiave API, e il messaggio finisce nei log e in failed_blocks
+impl From<reqwest::Error> for IndexerError {
+    fn from(e: reqwest::Error) -> Self {
+        //le cause (connessione rifiutata, timeout...) non sono nel Display di reqwest, le aggiungo io
+        let e = e.without_url();
+        let mut message = e.to_string();
+        let mut source = std::error::Error::source(&e);
+        while let Some(cause) = source {
+            message.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        IndexerError::Transport(message)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for IndexerError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        IndexerError::Transport(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for IndexerError {
+    fn from(e: std::io::Error) -> Self {
+        IndexerError::Transport(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for IndexerError {
+    fn from(e: serde_json::Error) -> Self {
+        IndexerError::Decode(e.to_string())
+    }
+}
+
+impl From<std::num::ParseIntError> for IndexerError {
+    fn from(e: std::num::ParseIntError) -> Self {
+        IndexerError::Decode(e.to_string())
+    }
+}
@@ -0,0 +1,465 @@
+use async_graphql::{
+    scalar, Context, EmptyMutation, EmptySubscription, InputObject, InputValueError, InputValueResult, Object, Scalar,
+    ScalarType, Schema, Value
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use crate::queries::{self, BlockRow, LogRow, TransactionRow};
+use crate::quantity::Quantity;
+
+//sottoinsieme dello schema GraphQL di Ethereum (EIP-1767, quello servito da geth) costruito solo
+//sui dati del db: block, transaction, log e account. I campi che richiedono lo stato del nodo
+//(balance, code, storage, gasPrice, syncing) non ci sono
+
+//massimo numero di blocchi per `blocks` e di log per `logs`
+const MAX_BLOCK_RANGE: i64 = 1000;
+const MAX_LOGS: i64 = 10_000;
+
+//limiti sulla forma della query, l'endpoint e' pubblico: la profondita' lascia passare
+//l'introspection di GraphiQL (circa 14 livelli), la complessita' ferma le liste annidate.
+//ogni campo lista conta LIST_COMPLEXITY volte i suoi figli
+const MAX_DEPTH: usize = 16;
+const MAX_COMPLEXITY: usize = 5000;
+const LIST_COMPLEXITY: usize = 10;
+
+pub type IndexSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn schema(db: Arc<PgPool>, chain_id: i64) -> IndexSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(GqlContext { db, chain_id })
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+struct GqlContext {
+    db: Arc<PgPool>,
+    chain_id: i64,
+}
+
+fn context<'a>(ctx: &'a Context<'_>) -> &'a GqlContext {
+    //inserito da schema(), c'e' sempre
+    ctx.data_unchecked::<GqlContext>()
+}
+
+
+//scalari dell'EIP-1767: stringhe esadecimali con 0x
+#[derive(Serialize, Deserialize)]
+pub struct Bytes32(pub String);
+scalar!(Bytes32);
+
+#[derive(Serialize, Deserialize)]
+pub struct Address(pub String);
+scalar!(Address);
+
+#[derive(Serialize, Deserialize)]
+pub struct Bytes(pub String);
+scalar!(Bytes);
+
+//in uscita sempre esadecimale, i valori sul db sono stringhe decimali
+#[derive(Serialize, Deserialize)]
+pub struct BigInt(pub String);
+scalar!(BigInt);
+
+//intero a 64 bit come in geth: in uscita stringa esadecimale, in ingresso numero, stringa decimale
+//o esadecimale (con le regole strette delle quantita' JSON-RPC)
+#[derive(Debug)]
+pub struct Long(pub i64);
+
+#[Scalar]
+impl ScalarType for Long {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let parsed = match &value {
+            Value::Number(n) => n.as_i64(),
+            Value::String(s) if s.starts_with("0x") => Quantity::from_hex(s).and_then(|q| q.to_int::<i64>()).ok(),
+            Value::String(s) => s.parse::<i64>().ok(),
+            _ => return Err(InputValueError::expected_type(value)),
+        };
+        parsed.map(Long).ok_or_else(|| InputValueError::custom(format!("invalid Long: {}", value)))
+    }
+    
+    fn to_value(&self) -> Value {
+        Value::String(format!("{:#x}", self.0))
+    }
+}
+
+impl BigInt {
+    fn from_decimal(value: &str) -> Self {
+        BigInt(Quantity::from_decimal(value).map(|q| q.to_hex()).unwrap_or_else(|_| value.to_string()))
+    }
+}
+
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    //blocco per numero o hash, senza argomenti l'ultimo indicizzato
+    async fn block(&self, ctx: &Context<'_>, number: Option<Long>, hash: Option<Bytes32>) -> async_graphql::Result<Option<Block>> {
+        let c = context(ctx);
+        let row = match (number, hash) {
+            (_, Some(hash)) => queries::get_block_by_hash(&c.db, c.chain_id, &hash.0).await?,
+            (Some(number), None) => queries::get_block_by_number(&c.db, c.chain_id, number.0).await?,
+            (None, None) => {
+                let last = crate::db::get_last_indexed_block(&c.db, c.chain_id).await?;
+                queries::get_block_by_number(&c.db, c.chain_id, last).await?
+            }
+        };
+        Ok(row.map(Block))
+    }
+    
+    //blocchi da `from` a `to` compresi
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity")]
+    async fn blocks(&self, ctx: &Context<'_>, from: Long, to: Option<Long>) -> async_graphql::Result<Vec<Block>> {
+        let c = context(ctx);
+        let to = match to {
+            Some(to) => to.0,
+            None => crate::db::get_last_indexed_block(&c.db, c.chain_id).await?,
+        };
+        //checked_sub: con from vicino a i64::MIN la differenza andrebbe in overflow
+        match to.checked_sub(from.0) {
+            Some(span) if span < 0 => return Err("`to` is before `from`".into()),
+            Some(span) if span < MAX_BLOCK_RANGE => {}
+            _ => return Err(format!("block range too large, max {}", MAX_BLOCK_RANGE).into()),
+        }
+        let rows = queries::get_blocks(&c.db, c.chain_id, from.0, to, MAX_BLOCK_RANGE).await?;
+        Ok(rows.into_iter().map(Block).collect())
+    }
+    
+    async fn transaction(&self, ctx: &Context<'_>, hash: Bytes32) -> async_graphql::Result<Option<Transaction>> {
+        let c = context(ctx);
+        Ok(queries::get_transaction(&c.db, c.chain_id, &hash.0).await?.map(Transaction))
+    }
+    
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity")]
+    async fn logs(&self, ctx: &Context<'_>, filter: FilterCriteria) -> async_graphql::Result<Vec<Log>> {
+        let c = context(ctx);
+        let last = crate::db::get_last_indexed_block(&c.db, c.chain_id).await?;
+        let from = filter.from_block.map(|b| b.0).unwrap_or(last);
+        let to = filter.to_block.map(|b| b.0).unwrap_or(last);
+        logs(c, from, to, filter.addresses, filter.topics).await
+    }
+    
+    #[graphql(name = "chainID")]
+    async fn chain_id(&self, ctx: &Context<'_>) -> BigInt {
+        BigInt(format!("{:#x}", context(ctx).chain_id))
+    }
+}
+
+
+#[derive(InputObject)]
+pub struct FilterCriteria {
+    from_block: Option<Long>,
+    to_block: Option<Long>,
+    addresses: Option<Vec<Address>>,
+    topics: Option<Vec<Vec<Bytes32>>>,
+}
+
+#[derive(InputObject)]
+pub struct BlockFilterCriteria {
+    addresses: Option<Vec<Address>>,
+    topics: Option<Vec<Vec<Bytes32>>>,
+}
+
+async fn logs(
+    c: &GqlContext,
+    from: i64,
+    to: i64,
+    addresses: Option<Vec<Address>>,
+    topics: Option<Vec<Vec<Bytes32>>>
+) -> async_graphql::Result<Vec<Log>> {
+    let addresses: Vec<String> = addresses.unwrap_or_default().into_iter().map(|a| a.0).collect();
+    let topics: Vec<Vec<String>> = topics.unwrap_or_default()
+        .into_iter()
+        .map(|options| options.into_iter().map(|t| t.0).collect())
+        .collect();
+    
+    let rows = queries::get_logs(&c.db, c.chain_id, from, to, &addresses, &topics, MAX_LOGS + 1).await?;
+    if rows.len() as i64 > MAX_LOGS {
+        return Err(format!("query returned more than {} logs", MAX_LOGS).into());
+    }
+    Ok(rows.into_iter().map(Log).collect())
+}
+
+
+//solo l'indirizzo: saldo, codice e storage richiedono lo stato del nodo
+pub struct Account(String);
+
+#[Object]
+impl Account {
+    async fn address(&self) -> Address {
+        Address(self.0.clone())
+    }
+}
+
+
+pub struct Block(BlockRow);
+
+#[Object]
+impl Block {
+    async fn number(&self) -> Long {
+        Long(self.0.number)
+    }
+    
+    async fn hash(&self) -> Bytes32 {
+        Bytes32(self.0.hash.clone())
+    }
+    
+    async fn parent(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Block>> {
+        let c = context(ctx);
+        Ok(queries::get_block_by_hash(&c.db, c.chain_id, &self.0.parent_hash).await?.map(Block))
+    }
+    
+    async fn miner(&self) -> Account {
+        Account(self.0.miner.clone())
+    }
+    
+    async fn gas_limit(&self) -> Long {
+        Long(self.0.gas_limit)
+    }
+    
+    async fn gas_used(&self) -> Long {
+        Long(self.0.gas_used)
+    }
+    
+    async fn timestamp(&self) -> Long {
+        Long(self.0.timestamp)
+    }
+    
+    async fn raw_size(&self) -> Long {
+        Long(self.0.size)
+    }
+    
+    async fn difficulty(&self) -> Option<BigInt> {
+        self.0.difficulty.as_deref().map(BigInt::from_decimal)
+    }
+    
+    async fn total_difficulty(&self) -> Option<BigInt> {
+        self.0.total_difficulty.as_deref().map(BigInt::from_decimal)
+    }
+    
+    async fn transaction_count(&self) -> Long {
+        Long(self.0.transactions_count.into())
+    }
+    
+    //campi dell'header, nulli per i blocchi salvati prima che venissero indicizzati
+    async fn nonce(&self) -> Option<Bytes> {
+        self.0.nonce.clone().map(Bytes)
+    }
+    
+    async fn state_root(&self) -> Option<Bytes32> {
+        self.0.state_root.clone().map(Bytes32)
+    }
+    
+    async fn transactions_root(&self) -> Option<Bytes32> {
+        self.0.transactions_root.clone().map(Bytes32)
+    }
+    
+    async fn receipts_root(&self) -> Option<Bytes32> {
+        self.0.receipts_root.clone().map(Bytes32)
+    }
+    
+    async fn logs_bloom(&self) -> Option<Bytes> {
+        self.0.logs_bloom.clone().map(Bytes)
+    }
+    
+    async fn extra_data(&self) -> Option<Bytes> {
+        self.0.extra_data.clone().map(Bytes)
+    }
+    
+    async fn mix_hash(&self) -> Option<Bytes32> {
+        self.0.mix_hash.clone().map(Bytes32)
+    }
+    
+    async fn base_fee_per_gas(&self) -> Option<BigInt> {
+        self.0.base_fee_per_gas.as_deref().map(BigInt::from_decimal)
+    }
+    
+    async fn withdrawals_root(&self) -> Option<Bytes32> {
+        self.0.withdrawals_root.clone().map(Bytes32)
+    }
+    
+    async fn blob_gas_used(&self) -> Option<Long> {
+        self.0.blob_gas_used.map(Long)
+    }
+    
+    async fn excess_blob_gas(&self) -> Option<Long> {
+        self.0.excess_blob_gas.map(Long)
+    }
+    
+    async fn parent_beacon_block_root(&self) -> Option<Bytes32> {
+        self.0.parent_beacon_block_root.clone().map(Bytes32)
+    }
+    
+    async fn requests_hash(&self) -> Option<Bytes32> {
+        self.0.requests_hash.clone().map(Bytes32)
+    }
+    
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity")]
+    async fn transactions(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Transaction>> {
+        let c = context(ctx);
+        let rows = queries::get_block_transactions(&c.db, c.chain_id, self.0.number).await?;
+        Ok(rows.into_iter().map(Transaction).collect())
+    }
+    
+    async fn transaction_at(&self, ctx: &Context<'_>, index: Long) -> async_graphql::Result<Option<Transaction>> {
+        //un indice fuori dal range di transaction_index non puo' esistere nel blocco
+        let Ok(index) = i32::try_from(index.0) else {
+            return Ok(None);
+        };
+        let c = context(ctx);
+        Ok(queries::get_block_transaction_at(&c.db, c.chain_id, self.0.number, index).await?.map(Transaction))
+    }
+    
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity")]
+    async fn logs(&self, ctx: &Context<'_>, filter: BlockFilterCriteria) -> async_graphql::Result<Vec<Log>> {
+        logs(context(ctx), self.0.number, self.0.number, filter.addresses, filter.topics).await
+    }
+    
+    async fn account(&self, address: Address) -> Account {
+        Account(address.0.to_lowercase())
+    }
+}
+
+
+pub struct Transaction(TransactionRow);
+
+impl Transaction {
+    async fn receipt(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<queries::ReceiptRow>> {
+        let c = context(ctx);
+        Ok(queries::get_receipt(&c.db, c.chain_id, &self.0.hash).await?)
+    }
+}
+
+#[Object]
+impl Transaction {
+    async fn hash(&self) -> Bytes32 {
+        Bytes32(self.0.hash.clone())
+    }
+    
+    async fn nonce(&self) -> Long {
+        Long(self.0.nonce)
+    }
+    
+    async fn index(&self) -> Long {
+        Long(self.0.transaction_index.into())
+    }
+    
+    async fn from(&self) -> Account {
+        Account(self.0.from_address.clone())
+    }
+    
+    async fn to(&self) -> Option<Account> {
+        self.0.to_address.clone().map(Account)
+    }
+    
+    async fn value(&self) -> BigInt {
+        BigInt::from_decimal(&self.0.value)
+    }
+    
+    //per le transazioni EIP-1559 i nodi restituiscono gia' il prezzo effettivo in gasPrice
+    async fn gas_price(&self) -> Option<BigInt> {
+        self.0.gas_price.as_deref().map(BigInt::from_decimal)
+    }
+    
+    async fn max_fee_per_gas(&self) -> Option<BigInt> {
+        self.0.max_fee_per_gas.as_deref().map(BigInt::from_decimal)
+    }
+    
+    async fn max_priority_fee_per_gas(&self) -> Option<BigInt> {
+        self.0.max_priority_fee_per_gas.as_deref().map(BigInt::from_decimal)
+    }
+    
+    async fn gas(&self) -> Long {
+        Long(self.0.gas)
+    }
+    
+    async fn input_data(&self) -> Bytes {
+        Bytes(self.0.input.clone())
+    }
+    
+    #[graphql(name = "type")]
+    async fn tx_type(&self) -> Option<Long> {
+        self.0.tx_type.map(|t| Long(t.into()))
+    }
+    
+    async fn block(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Block>> {
+        let c = context(ctx);
+        Ok(queries::get_block_by_number(&c.db, c.chain_id, self.0.block_number).await?.map(Block))
+    }
+    
+    async fn status(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Long>> {
+        Ok(self.receipt(ctx).await?.and_then(|r| r.status).map(|s| Long(s.into())))
+    }
+    
+    async fn gas_used(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Long>> {
+        Ok(self.receipt(ctx).await?.map(|r| Long(r.gas_used)))
+    }
+    
+    async fn cumulative_gas_used(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Long>> {
+        Ok(self.receipt(ctx).await?.map(|r| Long(r.cumulative_gas_used)))
+    }
+    
+    async fn effective_gas_price(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<BigInt>> {
+        Ok(self.receipt(ctx).await?.and_then(|r| r.effective_gas_price).map(|p| BigInt::from_decimal(&p)))
+    }
+    
+    async fn created_contract(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Account>> {
+        Ok(self.receipt(ctx).await?.and_then(|r| r.contract_address).map(Account))
+    }
+    
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity")]
+    async fn logs(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Vec<Log>>> {
+        let c = context(ctx);
+        let rows = queries::get_transaction_logs(&c.db, c.chain_id, &self.0.hash).await?;
+        Ok(Some(rows.into_iter().map(Log).collect()))
+    }
+}
+
+
+pub struct Log(LogRow);
+
+#[Object]
+impl Log {
+    async fn index(&self) -> Long {
+        Long(self.0.log_index.into())
+    }
+    
+    async fn account(&self) -> Account {
+        Account(self.0.address.clone())
+    }
+    
+    async fn topics(&self) -> Vec<Bytes32> {
+        [&self.0.topic0, &self.0.topic1, &self.0.topic2, &self.0.topic3]
+            .into_iter()
+            .flatten()
+            .map(|t| Bytes32(t.clone()))
+            .collect()
+    }
+    
+    async fn data(&self) -> Bytes {
+        Bytes(self.0.data.clone())
+    }
+    
+    async fn transaction(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Transaction>> {
+        let c = context(ctx);
+        Ok(queries::get_transaction(&c.db, c.chain_id, &self.0.transaction_hash).await?.map(Transaction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_parses_like_geth() {
+        for input in [Value::from(436), Value::from("436"), Value::from("0x1b4")] {
+            assert_eq!(Long::parse(input).unwrap().0, 436);
+        }
+        for input in [Value::from("0x01b4"), Value::from("0x"), Value::from("1b4"), Value::from(1.5), Value::from(true)] {
+            assert!(Long::parse(input).is_err());
+        }
+        assert_eq!(Long(436).to_value(), Value::from("0x1b4"));
+    }
+}
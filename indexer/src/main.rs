@@ -2,153 +2,461 @@ mod models;
 mod db;
 mod alchemy;
 mod utils;
+mod reorg;
+mod pipeline;
+mod provider;
+mod chain;
+mod migrations;
+mod retry;
+mod audit;
+mod tokens;
+mod contracts;
+mod abi;
+mod signatures;
+mod queries;
+mod api;
+mod graphql;
+mod proxy;
+mod metrics;
+mod config;
+mod error;
+mod quantity;
  
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use dotenv::dotenv;
 use std::env;
-use std::time::Duration;
 use std::sync::Arc;
 use sqlx::PgPool;
+use config::Config;
+use error::IndexerError;
 use std::pin::Pin;
+use std::time::Duration;
+use provider::EthProvider;
+use pipeline::WriteMode;
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+
+
+//riga di comando: senza sottocomando fa il sync continuo come prima
+#[derive(Parser)]
+#[command(version, about = "Ethereum block indexer")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    
+    #[command(flatten)]
+    options: SyncOptions,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Catch up to the chain head, then follow new blocks (default)
+    Sync,
+    /// Index a fixed range of blocks, keeping rows that are already stored
+    Backfill {
+        /// First block of the range
+        #[arg(long, allow_negative_numbers = true, value_parser = clap::value_parser!(i64).range(0..))]
+        from: i64,
+        /// Last block of the range (default: head minus confirmations)
+        #[arg(long, allow_negative_numbers = true, value_parser = clap::value_parser!(i64).range(0..))]
+        to: Option<i64>,
+    },
+    /// Re-download a range of blocks and overwrite the stored rows
+    Reindex {
+        /// First block of the range
+        #[arg(long, allow_negative_numbers = true, value_parser = clap::value_parser!(i64).range(0..))]
+        from: i64,
+        /// Last block of the range (default: head minus confirmations)
+        #[arg(long, allow_negative_numbers = true, value_parser = clap::value_parser!(i64).range(0..))]
+        to: Option<i64>,
+    },
+    /// Print the sync state as JSON
+    Status,
+    /// Look for gaps and broken parent links and repair them
+    #[command(alias = "audit")]
+    Verify {
+        /// Only print the report
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Serve the REST, GraphQL and JSON-RPC APIs without indexing
+    Serve,
+}
+
+//opzioni comuni a tutti i sottocomandi, vincono sul file di configurazione e sulle variabili d'ambiente
+#[derive(Args)]
+struct SyncOptions {
+    /// Config file (default: indexer.toml if it exists)
+    #[arg(long, global = true, env = "CONFIG_FILE")]
+    config: Option<String>,
+    
+    /// First block to index the first time a chain is synced (default: chain head) [config: chain.start_block]
+    #[arg(long, global = true)]
+    start_block: Option<i64>,
+    
+    /// Blocks downloaded in parallel [config: sync.concurrency, default 10]
+    #[arg(long, global = true)]
+    concurrency: Option<usize>,
+    
+    /// Blocks to stay behind the chain head, so that short reorgs never reach the db [config: chain.confirmations]
+    #[arg(long, global = true)]
+    confirmations: Option<i64>,
+}
+
+//indirizzo delle API se API_ADDR non e' impostato (solo per `serve`, nel sync le API sono opzionali)
+const DEFAULT_API_ADDR: &str = "127.0.0.1:8080";
 
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     dotenv().ok();
     
+    //dopo dotenv, cosi' anche le variabili del file .env fanno da default per le opzioni
+    let cli = Cli::parse();
+    
+    //un intervallo rovesciato si scopre prima di aprire il provider: i blocchi non finiscono in failed_blocks
+    if let Some(Command::Backfill { from, to: Some(to) } | Command::Reindex { from, to: Some(to) }) = &cli.command {
+        if from > to {
+            Cli::command().error(ErrorKind::ValueValidation, format!("--from {} is after --to {}", from, to)).exit();
+        }
+    }
+    init_logging();
+    
+    //configurazione controllata prima di toccare db e provider: in caso di errori li elenco tutti ed esco
+    let config = match load_config(&cli.options) {
+        Ok(config) => config,
+        Err(errors) => {
+            eprintln!("invalid configuration:");
+            for e in errors {
+                eprintln!("  - {}", e.trim_end().replace('\n', "\n    "));
+            }
+            std::process::exit(2);
+        }
+    };
+    config::set_pipelines(config.pipelines.clone());
+    
     //db setup:
-    let db_conn = format!(
-        "postgres://{}:{}@{}/{}",
-        env::var("DB_USER")?,
-        env::var("DB_PASSWORD")?,
-        env::var("DB_HOST")?,
-        env::var("DB_NAME")?
-    );
+    let db_pool = PgPool::connect_with(config.connect_options()).await?;
+    info!("database connected");
     
-
-    let db_pool = PgPool::connect(&db_conn).await?;
-    println!("database connected");
+    //creo o aggiorno lo schema, niente SQL a mano sui nuovi ambienti
+    migrations::run(&db_pool).await?;
     
     let db_pool = Arc::new(db_pool);
 
 //---------------------------------------------------------------------------------
-    //setup AlchemyClient
-    let api_key = env::var("ALCHEMY_API_KEY").expect("error");
-    let alchemy_http = Arc::new(alchemy::AlchemyClient::new(api_key.clone()));
+    //chain su cui lavoro, ogni riga sul db e' salvata con questo chain id
+    let chain_id = config.chain_id()?;
+    info!(chain_id, "chain configured");
+    
+    //registro dei selettori per decodificare l'input delle transazioni
+    if config.pipelines.decoded_calls {
+        let loaded = signatures::load(&db_pool, config.pipelines.signatures_file.as_deref()).await?;
+        info!(count = loaded, "function signatures loaded");
+    }
+    
+    //ABI dei contratti da decodificare: i file in abi_dir vengono registrati nella tabella abis
+    if let Some(dir) = &config.pipelines.abi_dir {
+        let loaded = abi::load_dir(&db_pool, chain_id, std::path::Path::new(dir)).await?;
+        info!(count = loaded, %dir, "ABIs loaded");
+    }
+    
+    //setup provider: http(s), ws(s) o il path del socket IPC di un nodo locale
+    let (rpc_url, ws_url) = config.provider.urls(chain_id)?;
+    let batch_size = config.provider.batch_size;
+    let command = cli.command.unwrap_or(Command::Sync);
+    
+    if rpc_url.starts_with("http://") || rpc_url.starts_with("https://") {
+        run(Arc::new(provider::HttpProvider::new(rpc_url, batch_size)), db_pool, &config, chain_id, ws_url, command).await
+    } else if rpc_url.starts_with("ws://") || rpc_url.starts_with("wss://") {
+        //se non c'e' un WS_URL separato mi iscrivo ai nuovi blocchi sullo stesso endpoint
+        let ws_url = ws_url.or_else(|| Some(rpc_url.clone()));
+        run(Arc::new(provider::WsProvider::new(rpc_url, batch_size)), db_pool, &config, chain_id, ws_url, command).await
+    } else {
+        let path = rpc_url.trim_start_matches("ipc://").to_string();
+        run(Arc::new(provider::IpcProvider::new(path, batch_size)), db_pool, &config, chain_id, ws_url, command).await
+    }
+}
+
+
+//file di configurazione e variabili d'ambiente, poi le opzioni della riga di comando sopra tutto
+fn load_config(options: &SyncOptions) -> Result<Config, Vec<String>> {
+    let mut config = Config::load(options.config.as_deref())?;
+    
+    if let Some(start_block) = options.start_block {
+        config.chain.start_block = Some(start_block);
+    }
+    if let Some(concurrency) = options.concurrency {
+        config.sync.concurrency = concurrency;
+    }
+    if let Some(confirmations) = options.confirmations {
+        config.chain.confirmations = confirmations;
+    }
+    
+    config.validate()?;
+    Ok(config)
+}
+
+
+//log strutturati: livello da RUST_LOG (default info), formato da LOG_FORMAT
+//(json per la pipeline dei log, text per leggerli in console)
+fn init_logging() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    //su stderr, cosi' stdout resta libero per l'output dei comandi (status)
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr);
+    
+    match env::var("LOG_FORMAT").as_deref() {
+        Ok("text") => builder.init(),
+        _ => builder.json().with_current_span(true).with_span_list(false).init(),
+    }
+}
+
+
+//esegue il sottocomando scelto, uguale per ogni tipo di provider
+async fn run<P: EthProvider>(
+    provider: Arc<P>,
+    db_pool: Arc<PgPool>,
+    config: &Config,
+    chain_id: i64,
+    ws_url: Option<String>,
+    command: Command
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    
+    //prima di tutto controllo che l'endpoint sia sulla chain giusta
+    chain::verify_chain_id(provider.as_ref(), chain_id).await?;
+    
+    match command {
+        Command::Sync => sync(provider, db_pool, config, chain_id, ws_url).await,
+        Command::Backfill { from, to } => index_range(&provider, &db_pool, config, chain_id, from, to, WriteMode::Insert).await,
+        Command::Reindex { from, to } => index_range(&provider, &db_pool, config, chain_id, from, to, WriteMode::Overwrite).await,
+        Command::Status => status(provider.as_ref(), &db_pool, config, chain_id).await,
+        Command::Verify { dry_run } => Ok(audit::run(provider.as_ref(), &db_pool, chain_id, !dry_run).await?),
+        Command::Serve => {
+            let addr = config.api.addr.clone().unwrap_or_else(|| DEFAULT_API_ADDR.to_string());
+            Ok(api::serve(provider, db_pool, chain_id, addr).await?)
+        }
+    }
+}
+
+
+//testa della catena meno le conferme: oltre questo blocco non indicizzo.
+//se il provider non risponde ritento con il backoff invece di uscire subito
+async fn confirmed_head<P: EthProvider>(
+    provider: &P,
+    config: &Config
+) -> Result<i64, IndexerError> {
+    let head = retry::with_backoff(&config.retry_policy(), "head", || provider.get_latest_block_number()).await?;
+    Ok(head - config.chain.confirmations)
+}
+
+
+//backfill (Insert) e reindex (Overwrite) di un intervallo fisso, poi esco
+async fn index_range<P: EthProvider>(
+    provider: &Arc<P>,
+    db_pool: &PgPool,
+    config: &Config,
+    chain_id: i64,
+    from: i64,
+    to: Option<i64>,
+    mode: WriteMode
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    
+    //anche un intervallo esplicito non va oltre i blocchi confermati
+    let head = confirmed_head(provider.as_ref(), config).await?;
+    let to = match to {
+        Some(to) if to > head => {
+            warn!(to, confirmed_head = head, "range goes past the confirmed head, stopping there");
+            head
+        }
+        Some(to) => to,
+        None => head,
+    };
+    if from > to {
+        return Err(format!("empty range: from {} to {}", from, to).into());
+    }
+    
+    //se la chain non e' ancora sul db il sync partira' da start_block o dall'inizio dell'intervallo:
+    //backfill e reindex non avanzano last_block_indexed, che resta del sync contiguo
+    if db::init_indexer_state(db_pool, chain_id, config.chain.start_block.unwrap_or(from) - 1).await? {
+        info!(chain_id, "new chain, indexer state created");
+    }
+    
+    let retry_policy = config.retry_policy();
+    info!(from, to, overwrite = mode == WriteMode::Overwrite, "indexing range");
+    
+    let last = pipeline::catch_up(provider, db_pool, chain_id, from..=to, config.sync.concurrency, &retry_policy, mode).await?;
+    if last < to {
+        return Err(format!("stopped at block {} of {}", last, to).into());
+    }
+    
+    let failed = db::count_failed_blocks(db_pool, chain_id).await?;
+    info!(from, to, failed_blocks = failed, "range indexed");
+    Ok(())
+}
+
+
+//stato del sync in JSON su stdout (i log vanno su stderr), per script e controlli
+async fn status<P: EthProvider>(
+    provider: &P,
+    db_pool: &PgPool,
+    config: &Config,
+    chain_id: i64
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    
+    let confirmations = config.chain.confirmations;
+    let last_indexed = db::get_last_indexed_block(db_pool, chain_id).await?;
+    let head = provider.get_latest_block_number().await?;
+    let failed = db::count_failed_blocks(db_pool, chain_id).await?;
+    
+    let status = serde_json::json!({
+        "chain_id": chain_id,
+        "last_block_indexed": last_indexed,
+        "head": head,
+        "confirmations": confirmations,
+        "lag": head - confirmations - last_indexed,
+        "failed_blocks": failed,
+    });
+    println!("{}", serde_json::to_string_pretty(&status)?);
+    Ok(())
+}
+
+
+//catch-up e poi sync dei nuovi blocchi
+async fn sync<P: EthProvider>(
+    provider: Arc<P>,
+    db_pool: Arc<PgPool>,
+    config: &Config,
+    chain_id: i64,
+    ws_url: Option<String>
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    
+    //con api.addr le API girano insieme al sync
+    if let Some(addr) = config.api.addr.clone() {
+        let api = api::serve(Arc::clone(&provider), Arc::clone(&db_pool), chain_id, addr);
+        tokio::spawn(async move {
+            if let Err(e) = api.await {
+                error!(kind = e.kind(), error = %e, "REST API stopped");
+            }
+        });
+    }
+    
+    let latest_on_chain = confirmed_head(provider.as_ref(), config).await?;
+    
+    //al primo avvio su questa chain parto da start_block, o dalla testa della catena se non c'e'
+    let start_block = config.chain.start_block.unwrap_or(latest_on_chain);
+    if db::init_indexer_state(&db_pool, chain_id, start_block - 1).await? {
+        info!(chain_id, start_block, "new chain, starting from block");
+    }
     
     //serve per verificare se siamo up to date oppure bisogna fare catch up
-    let last_indexed = db::get_last_indexed_block(&db_pool).await?;
-    let latest_on_chain = alchemy_http.get_latest_block_number().await?;
+    let last_indexed = db::get_last_indexed_block(&db_pool, chain_id).await?;
+    
+    info!(last_indexed, latest_on_chain, confirmations = config.chain.confirmations, "sync state");
+    metrics::set_last_indexed(last_indexed);
     
-    println!("last indexed: {}", last_indexed);
-    println!("latest on chain: {}", latest_on_chain);
+    //quanti blocchi scaricare in parallelo durante il catch-up
+    let concurrency = config.sync.concurrency;
+    let confirmations = config.chain.confirmations;
+    let retry_policy = Arc::new(config.retry_policy());
+    
+    //in background riprovo i blocchi finiti in failed_blocks
+    tokio::spawn(retry::drain_failed_blocks(
+        Arc::clone(&provider),
+        Arc::clone(&db_pool),
+        chain_id,
+        Duration::from_secs(30),
+    ));
     
     let gap = latest_on_chain - last_indexed;
     if gap > 0 {
-        println!("gap detected: {} blocks", gap);
+        info!(gap, "catching up");
         
-        for block_num in (last_indexed + 1)..=latest_on_chain {
-
-            //salvo ogni blocco chiamando il metodo index_blockchain
-            let res = index_block(&alchemy_http, &db_pool, block_num).await;
-            if let Ok(_) = res {
-                    // aggiorno la tabella che tiene traccia dell'ultimo blocco salvato
-                    db::update_last_indexed_block(&db_pool, block_num).await?;
-            } else if let Err(e) = res {
-                eprintln!("Error indexing block {}: {}. Skipping.", block_num, e);
-            }
+        //scarico i blocchi in parallelo e li salvo in ordine
+        let last = pipeline::catch_up(&provider, &db_pool, chain_id, (last_indexed + 1)..=latest_on_chain, concurrency, &retry_policy, WriteMode::Sync).await?;
+        
+        if last == latest_on_chain {
+            info!("catch-up complete");
+        } else {
+            warn!(last, "catch-up stopped early, will resume on the next block");
         }
-            
-        //rallento il loop
-        tokio::time::sleep(Duration::from_millis(100)).await;
-        println!("catch-up complete");
     } else {
-        println!("already up to date");
+        info!("already up to date");
     }
     
     //-------------------------------------------------------------------------------------------
     //parte webSocket
-    println!("webSocket sync");
-    let ws = alchemy::AlchemyWebSocket::new(api_key);
-
     //clono per usarli nel callback
-    let alchemy_clone = Arc::clone(&alchemy_http);
+    let provider_clone = Arc::clone(&provider);
     let db_clone = Arc::clone(&db_pool); 
+    let retry_clone = Arc::clone(&retry_policy);
 
     //definisco la callback, chiamata in futuro da subscribe new head
     let callback = move |block_hex: String|  -> Pin<Box<dyn std::future::Future<Output = ()> + Send + 'static>>{
     
         //clono per usarli dentro async block
-        let alchemy = Arc::clone(&alchemy_clone); 
+        let provider = Arc::clone(&provider_clone); 
         let db = Arc::clone(&db_clone); 
+        let retry = Arc::clone(&retry_clone);
     
         Box::pin(async move { 
             //il blocco che mi è arrivato esadecimale viene trasformato
-            let result = utils::hex_to_i64(&block_hex); 
+            let result = quantity::Quantity::from_hex(&block_hex).and_then(|q| q.to_int::<i64>()); 
 
             if let Ok(block_num) = result {
-                println!("new block: {}", block_num);
+                info!(block = block_num, "new block");
+                metrics::set_head(block_num);
+                
+                //mi fermo a `confirmations` blocchi dalla testa
+                let block_num = block_num - confirmations;
 
                 //metodo che viene chiamato per vedere sul db l'ultimo blocco salvato
-                let last_result = db::get_last_indexed_block(&db).await;
+                let last_result = db::get_last_indexed_block(&db, chain_id).await;
 
                 if let Ok(last_indexed) = last_result {
                     if block_num > last_indexed {
+                        //stessa pipeline del catch-up iniziale
+                        let res = pipeline::catch_up(&provider, &db, chain_id, (last_indexed + 1)..=block_num, concurrency, &retry, WriteMode::Sync).await;
 
-                        for num in (last_indexed + 1)..=block_num {
-                            //chiamo il metodo salva il nuovo blocco sul db
-                            let add_block = index_block(&alchemy, &db, num).await;
-
-                            if let Ok(_) = add_block {
-                                //update sul db per tener traccia dell'ultimo blocco 
-                                let update_db = db::update_last_indexed_block(&db, num).await;
-
-                                if let Ok(_) = update_db {
-                                    println!(" block {} indexed (from WS)", num);
-                                } else if let Err(e) = update_db {
-                                    eprintln!("error updating state: {}", e);
-                                }
-                            } else if let Err(e) = add_block {
-                                eprintln!("error indexing block: {}", e);
+                        match res {
+                            Ok(last) if last == block_num => info!(block = block_num, "new block indexed"),
+                            Ok(last) => warn!(block = block_num, last, "catch-up stopped early"),
+                            //i blocchi successivi finirebbero sul ramo orfano: esco invece di andare avanti
+                            Err(e @ IndexerError::DeepReorg(_)) => {
+                                error!(kind = e.kind(), block = block_num, error = %e, "sync halted");
+                                std::process::exit(1);
                             }
-                        } 
+                            Err(e) => error!(kind = e.kind(), block = block_num, error = %e, "error indexing block"),
+                        }
                     } else {
-                        println!("block {} already indexed", block_num);
+                        info!(block = block_num, "block already indexed");
                     }
                 } else if let Err(e) = last_result {
-                    eprintln!("error getting last indexed: {}", e);
-                    return;
+                    //il blocco non va perso: al prossimo blocco il catch-up riparte da last_indexed
+                    error!(kind = e.kind(), error = %e, "error getting last indexed block");
                 }
             } else if let Err(e) = result {
-                eprintln!("error parsing block number from WS: {}", e);
-                return;
+                error!(kind = "rpc_parse", error = %e, "error parsing block number from WS");
             }
-            () 
         }
     )
 };
     
 
-ws.subscribe_new_blocks(callback).await?;
+    match ws_url {
+        Some(url) => {
+            info!("following new blocks over WebSocket");
+            let ws = alchemy::AlchemyWebSocket::new(url);
+            ws.subscribe_new_blocks(callback).await?;
+        }
+        None => {
+            //nessun endpoint per eth_subscribe: controllo la testa della catena ogni pochi secondi
+            let interval = config.provider.poll_interval_secs;
+            info!(interval_secs = interval, "following new blocks by polling");
+            provider::poll_new_blocks(provider.as_ref(), Duration::from_secs(interval), callback).await?;
+        }
+    }
 
-Ok(())
+    Ok(())
 }
 
-
-//metodo che serve per prendere un blocco e salvo sul db
-async fn index_block(
-    alchemy: &alchemy::AlchemyClient,
-    db_pool: &PgPool, 
-    block_number: i64
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    
-    //richiedo il blocco
-    let block = alchemy.get_block(block_number).await?;
-    
-    //salvo il blocco 
-    let mut db_transazione = db_pool.begin().await?;
-    db::save_block(&mut db_transazione, &block).await?;
-    
-    db_transazione.commit().await?;
-    
-    Ok(())
-}
\ No newline at end of file
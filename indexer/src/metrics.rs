@@ -0,0 +1,79 @@
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge, Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, TextEncoder,
+};
+use std::sync::LazyLock;
+use tracing::error;
+
+//metriche Prometheus dell'indexer, esposte su /metrics dal server delle API.
+//registrate nel registry di default la prima volta che vengono usate
+
+pub static HEAD_BLOCK: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("indexer_head_block", "Latest block number seen on the chain").expect("metric registration")
+});
+
+pub static LAST_INDEXED_BLOCK: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("indexer_last_indexed_block", "Last block number committed to the database").expect("metric registration")
+});
+
+pub static LAG_BLOCKS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("indexer_lag_blocks", "Blocks between the chain head and the last indexed block").expect("metric registration")
+});
+
+pub static BLOCKS_INDEXED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("indexer_blocks_indexed_total", "Blocks committed to the database").expect("metric registration")
+});
+
+pub static RPC_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("indexer_rpc_requests_total", "JSON-RPC requests sent to the provider", &["method"])
+        .expect("metric registration")
+});
+
+pub static RPC_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "indexer_rpc_request_duration_seconds",
+        "JSON-RPC round trip latency (the whole batch for batched requests)",
+        &["method"]
+    )
+    .expect("metric registration")
+});
+
+//kind: transport (la richiesta non e' arrivata), rpc (oggetto error), missing (nessuna risposta nel batch)
+pub static RPC_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("indexer_rpc_errors_total", "Failed JSON-RPC requests", &["method", "kind"])
+        .expect("metric registration")
+});
+
+pub static WS_RECONNECTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("indexer_ws_reconnects_total", "WebSocket subscription reconnections").expect("metric registration")
+});
+
+pub static DB_COMMIT_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!("indexer_db_commit_duration_seconds", "Time to write and commit one block").expect("metric registration")
+});
+
+pub static REORGS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("indexer_reorgs_total", "Chain reorganizations rolled back").expect("metric registration")
+});
+
+
+//il lag dipende da entrambi i valori, lo aggiorno insieme a ciascuno
+pub fn set_head(block_number: i64) {
+    HEAD_BLOCK.set(block_number);
+    LAG_BLOCKS.set(block_number - LAST_INDEXED_BLOCK.get());
+}
+
+pub fn set_last_indexed(block_number: i64) {
+    LAST_INDEXED_BLOCK.set(block_number);
+    LAG_BLOCKS.set(HEAD_BLOCK.get() - block_number);
+}
+
+
+//formato testo di Prometheus
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        error!(error = %e, "error encoding metrics");
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -0,0 +1,80 @@
+use sqlx::{Executor, PgPool, Row};
+use crate::error::IndexerError;
+use tracing::info;
+
+//migrazioni incluse nel binario, applicate in ordine all'avvio.
+//per cambiare lo schema si aggiunge un nuovo file, quelli gia' applicati non vanno modificati
+const MIGRATIONS: &[(i32, &str, &str)] = &[
+    (1, "initial", include_str!("../migrations/0001_initial.sql")),
+    (2, "failed_blocks", include_str!("../migrations/0002_failed_blocks.sql")),
+    (3, "erc20", include_str!("../migrations/0003_erc20.sql")),
+    (4, "nft", include_str!("../migrations/0004_nft.sql")),
+    (5, "contracts", include_str!("../migrations/0005_contracts.sql")),
+    (6, "abi", include_str!("../migrations/0006_abi.sql")),
+    (7, "signatures", include_str!("../migrations/0007_signatures.sql")),
+    (8, "difficulty", include_str!("../migrations/0008_difficulty.sql")),
+    (9, "block_header", include_str!("../migrations/0009_block_header.sql")),
+    (10, "rpc_fields", include_str!("../migrations/0010_rpc_fields.sql")),
+];
+
+//chiave per pg_advisory_lock, evita che due indexer migrino lo stesso db insieme
+const MIGRATION_LOCK: i64 = 7_420_001;
+
+
+//applica le migrazioni che mancano, ognuna nella sua transazione
+pub async fn run(pool: &PgPool) -> Result<(), IndexerError> {
+    
+    let mut conn = pool.acquire().await?;
+    
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK)
+        .execute(&mut *conn)
+        .await?;
+    
+    let res = apply_pending(&mut conn).await;
+    
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK)
+        .execute(&mut *conn)
+        .await?;
+    
+    res
+}
+
+
+async fn apply_pending(
+    conn: &mut sqlx::PgConnection
+) -> Result<(), IndexerError> {
+    
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )"
+    ).await?;
+    
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0) FROM schema_migrations")
+        .fetch_one(&mut *conn)
+        .await?;
+    let current: i32 = row.get(0);
+    
+    for (version, name, sql) in MIGRATIONS.iter().filter(|(v, _, _)| *v > current) {
+        info!(version, name, "applying migration");
+        
+        let mut db_transazione = sqlx::Connection::begin(&mut *conn).await?;
+        
+        //eseguo il file come testo semplice, cosi' puo' contenere piu' statement
+        (&mut *db_transazione).execute(*sql).await?;
+        
+        sqlx::query("INSERT INTO schema_migrations (version, name) VALUES ($1, $2)")
+            .bind(version)
+            .bind(name)
+            .execute(&mut *db_transazione)
+            .await?;
+        
+        db_transazione.commit().await?;
+    }
+    
+    Ok(())
+}
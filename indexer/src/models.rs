@@ -1,49 +1,192 @@
-use serde::{Serialize, Deserialize};
-use serde_json::Value;
-
-
-//modulo per richieste a alchemy
-#[derive(Serialize)]
-pub struct JRPCRequest {
-    pub jsonrpc: String,
-    pub method: String,
-    pub params: Vec<Value>,
-    pub id: i32,
-}
-
-//modulo per risposte da alchemy
-
-#[derive(Deserialize, Debug)]
-pub struct JRPCResponse<T> {  
-    pub id: i32,
-    pub jsonrpc: String,
-    pub result: Option<T>,  
-    #[serde(default)]
-    pub error: Option<Value>, 
-}
-
-//struttura per quando mi arriva come risposta un blocco
-#[derive(Debug, Deserialize)]
-pub struct Block {
-    pub number: String,
-    pub hash: String,
-    #[serde(rename = "parentHash")]
-    pub parent_hash: String,
-    pub timestamp: String,
-    pub miner: String,
-    #[serde(rename = "gasUsed")]
-    pub gas_used: String,
-    #[serde(rename = "gasLimit")]
-    pub gas_limit: String,
-    #[serde(default)] 
-    pub transactions: Vec<Value>, 
-    pub size: String,
-}
-
-
-
-
-
-
-
-
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::quantity::Quantity;
+
+
+//modulo per richieste a alchemy (e agli altri provider)
+#[derive(Serialize)]
+pub struct JRPCRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Vec<Value>,
+    pub id: i32,
+}
+
+//modulo per risposte da alchemy e dagli altri provider
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+pub struct JRPCResponse<T> {  
+    pub id: i32,
+    pub jsonrpc: String,
+    pub result: Option<T>,  
+    #[serde(default)]
+    pub error: Option<Value>, 
+}
+
+//struttura per quando mi arriva come risposta un blocco
+#[derive(Debug, Deserialize)]
+pub struct Block {
+    pub number: Quantity,
+    pub hash: String,
+    #[serde(rename = "parentHash")]
+    pub parent_hash: String,
+    pub timestamp: Quantity,
+    pub miner: String,
+    #[serde(rename = "gasUsed")]
+    pub gas_used: Quantity,
+    #[serde(rename = "gasLimit")]
+    pub gas_limit: Quantity,
+    #[serde(default)] 
+    pub transactions: Vec<Transaction>, 
+    pub size: Quantity,
+    //0 dopo il merge, totalDifficulty manca sui client che l'hanno tolta
+    #[serde(default)]
+    pub difficulty: Option<Quantity>,
+    #[serde(rename = "totalDifficulty", default)]
+    pub total_difficulty: Option<Quantity>,
+    #[serde(rename = "stateRoot")]
+    pub state_root: String,
+    #[serde(rename = "transactionsRoot")]
+    pub transactions_root: String,
+    #[serde(rename = "receiptsRoot")]
+    pub receipts_root: String,
+    #[serde(rename = "logsBloom")]
+    pub logs_bloom: String,
+    #[serde(rename = "extraData")]
+    pub extra_data: String,
+    //prevRandao dopo il merge, alcuni client di chain PoA non restituiscono mixHash e nonce
+    #[serde(rename = "mixHash", default)]
+    pub mix_hash: Option<String>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    //campi aggiunti dai fork, assenti nei blocchi precedenti: London, Shanghai, Cancun, Prague
+    #[serde(rename = "baseFeePerGas", default)]
+    pub base_fee_per_gas: Option<Quantity>,
+    #[serde(rename = "withdrawalsRoot", default)]
+    pub withdrawals_root: Option<String>,
+    #[serde(rename = "blobGasUsed", default)]
+    pub blob_gas_used: Option<Quantity>,
+    #[serde(rename = "excessBlobGas", default)]
+    pub excess_blob_gas: Option<Quantity>,
+    #[serde(rename = "parentBeaconBlockRoot", default)]
+    pub parent_beacon_block_root: Option<String>,
+    #[serde(rename = "requestsHash", default)]
+    pub requests_hash: Option<String>,
+    #[serde(rename = "sha3Uncles", default)]
+    pub sha3_uncles: Option<String>,
+    #[serde(default)]
+    pub uncles: Vec<String>,
+    //oggetti {index, validatorIndex, address, amount} salvati come arrivano, solo da Shanghai
+    #[serde(default)]
+    pub withdrawals: Option<Vec<Value>>,
+}
+
+//struttura per le transazioni complete dentro al blocco (eth_getBlockByNumber con true)
+#[derive(Debug, Deserialize)]
+pub struct Transaction {
+    pub hash: String,
+    #[serde(rename = "blockNumber")]
+    pub block_number: Quantity,
+    #[serde(rename = "transactionIndex")]
+    pub transaction_index: Quantity,
+    pub from: String,
+    //None quando la transazione crea un contratto
+    pub to: Option<String>,
+    pub value: Quantity,
+    pub nonce: Quantity,
+    pub gas: Quantity,
+    //legacy e access list, per EIP-1559 e' il prezzo effettivo
+    #[serde(rename = "gasPrice")]
+    pub gas_price: Option<Quantity>,
+    #[serde(rename = "maxFeePerGas")]
+    pub max_fee_per_gas: Option<Quantity>,
+    #[serde(rename = "maxPriorityFeePerGas")]
+    pub max_priority_fee_per_gas: Option<Quantity>,
+    pub input: String,
+    #[serde(rename = "type")]
+    pub tx_type: Option<Quantity>,
+    #[serde(rename = "chainId")]
+    pub chain_id: Option<Quantity>,
+    //firma: yParity dalle access list in poi, v resta per compatibilita'
+    pub v: Option<Quantity>,
+    pub r: Option<Quantity>,
+    pub s: Option<Quantity>,
+    #[serde(rename = "yParity")]
+    pub y_parity: Option<Quantity>,
+    //liste salvate come arrivano dal nodo: tipo 1 e successivi, blob (tipo 3), autorizzazioni (tipo 4)
+    #[serde(rename = "accessList")]
+    pub access_list: Option<Value>,
+    #[serde(rename = "maxFeePerBlobGas")]
+    pub max_fee_per_blob_gas: Option<Quantity>,
+    #[serde(rename = "blobVersionedHashes")]
+    pub blob_versioned_hashes: Option<Value>,
+    #[serde(rename = "authorizationList")]
+    pub authorization_list: Option<Value>,
+}
+
+//struttura per la ricevuta di una transazione (eth_getBlockReceipts / eth_getTransactionReceipt)
+#[derive(Debug, Deserialize)]
+pub struct Receipt {
+    #[serde(rename = "transactionHash")]
+    pub transaction_hash: String,
+    #[serde(rename = "blockNumber")]
+    pub block_number: Quantity,
+    //None per i blocchi pre-Byzantium, che hanno la state root al suo posto
+    pub status: Option<Quantity>,
+    #[serde(rename = "gasUsed")]
+    pub gas_used: Quantity,
+    #[serde(rename = "cumulativeGasUsed")]
+    pub cumulative_gas_used: Quantity,
+    #[serde(rename = "effectiveGasPrice")]
+    pub effective_gas_price: Option<Quantity>,
+    #[serde(rename = "contractAddress")]
+    pub contract_address: Option<String>,
+    #[serde(default)]
+    pub logs: Vec<Log>,
+    #[serde(rename = "logsBloom")]
+    pub logs_bloom: Option<String>,
+    //state root al posto di status prima di Byzantium
+    pub root: Option<String>,
+    #[serde(rename = "blobGasUsed")]
+    pub blob_gas_used: Option<Quantity>,
+    #[serde(rename = "blobGasPrice")]
+    pub blob_gas_price: Option<Quantity>,
+}
+
+//struttura per gli eventi emessi dentro una ricevuta
+#[derive(Debug, Deserialize)]
+pub struct Log {
+    pub address: String,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    pub data: String,
+    #[serde(rename = "logIndex")]
+    pub log_index: Quantity,
+    #[serde(rename = "transactionHash")]
+    pub transaction_hash: String,
+    #[serde(rename = "blockNumber")]
+    pub block_number: Quantity,
+}
+
+//risultato di debug_traceBlockByNumber con callTracer, uno per transazione
+#[derive(Debug, Deserialize)]
+pub struct TxTrace {
+    #[serde(rename = "txHash")]
+    pub tx_hash: Option<String>,
+    pub result: CallFrame,
+}
+
+//una chiamata dentro la traccia, con le chiamate interne annidate
+#[derive(Debug, Deserialize)]
+pub struct CallFrame {
+    #[serde(rename = "type")]
+    pub call_type: String,
+    pub from: String,
+    pub to: Option<String>,
+    //presente quando la chiamata e' fallita (revert, out of gas...)
+    pub error: Option<String>,
+    #[serde(default)]
+    pub calls: Vec<CallFrame>,
+}
@@ -0,0 +1,282 @@
+use futures_util::stream::{self, StreamExt};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use crate::provider::EthProvider;
+use crate::models::{Block, Receipt};
+use crate::{config, contracts, db, metrics, reorg};
+use crate::contracts::Deployment;
+use crate::error::IndexerError;
+use crate::retry::{self, RetryPolicy};
+
+
+//blocco scaricato dal provider insieme alle sue ricevute, pronto per essere salvato
+pub struct FetchedBlock {
+    pub number: i64,
+    pub block: Block,
+    pub receipts: Vec<Receipt>,
+    pub contracts: Vec<Deployment>,
+}
+
+
+//come salvare un blocco: Insert lascia com'e' quello che e' gia' sul db (ON CONFLICT DO NOTHING),
+//Overwrite cancella le righe del blocco e le riscrive nella stessa transazione (reindex).
+//Sync e' Insert per il sync contiguo: solo li' si avanza last_block_indexed e un reorg fa il rollback
+//dei blocchi sopra l'antenato. Backfill, reindex e riparazioni lavorano su blocchi sparsi, quindi
+//non toccano lo stato e un genitore non canonico viene solo segnalato (lo sistema verify)
+#[derive(Clone, Copy, PartialEq)]
+pub enum WriteMode {
+    Sync,
+    Insert,
+    Overwrite,
+}
+
+
+//scarico i blocchi dell'intervallo in parallelo (al massimo `concurrency` richieste alla volta)
+//e li salvo in ordine. Ogni blocco viene ritentato con backoff, se continua a fallire finisce in
+//failed_blocks (ripreso poi in background) e si va avanti. Mi fermo se il provider non e' raggiungibile
+//(anche i blocchi dopo fallirebbero, riparto dal prossimo blocco) o se non riesco neanche a registrare
+//il blocco fallito, cosi' nessun buco resta senza traccia. Un reorg troppo profondo ferma tutto con
+//un errore: salvare i blocchi dopo significherebbe attaccarli al ramo orfano. Restituisce l'ultimo
+//blocco processato
+pub async fn catch_up<P: EthProvider>(
+    provider: &Arc<P>,
+    db_pool: &PgPool,
+    chain_id: i64,
+    blocks: RangeInclusive<i64>,
+    concurrency: usize,
+    retry: &RetryPolicy,
+    mode: WriteMode
+) -> Result<i64, IndexerError> {
+    
+    let to = *blocks.end();
+    let mut last_committed = blocks.start() - 1;
+    
+    //buffered mantiene l'ordine dei blocchi anche se le richieste finiscono in ordine diverso
+    let mut fetched = stream::iter(blocks)
+        .map(|num| {
+            let provider = Arc::clone(provider);
+            async move {
+                let res = retry::with_backoff(retry, "fetch", || fetch_block(provider.as_ref(), num))
+                    .instrument(info_span!("block", number = num, stage = "fetch"))
+                    .await;
+                (num, res)
+            }
+        })
+        .buffered(concurrency.max(1));
+    
+    while let Some((num, res)) = fetched.next().await {
+        let res = match res {
+            Ok(fetched_block) => {
+                let span = info_span!("block", number = num, hash = %fetched_block.block.hash, stage = "commit");
+                retry::with_backoff(retry, "commit", || commit_block(provider.as_ref(), db_pool, chain_id, &fetched_block, mode))
+                    .instrument(span)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        
+        if let Err(e) = res {
+            if let IndexerError::DeepReorg(_) = e {
+                error!(kind = e.kind(), block = num, error = %e, stopped_at = last_committed, "reorg too deep, stopping for manual inspection");
+                return Err(e);
+            }
+            if let IndexerError::Transport(_) = e {
+                error!(kind = e.kind(), block = num, error = %e, stopped_at = last_committed, "provider unreachable, stopping");
+                break;
+            }
+            
+            //gli errori non transitori non sono stati ritentati
+            let attempts = if e.is_transient() { retry.attempts } else { 1 };
+            error!(kind = e.kind(), block = num, attempts, error = %e, "block failed, moving it to failed_blocks");
+            if let Err(db_err) = db::save_failed_block(db_pool, chain_id, num, &e.to_string(), attempts as i32).await {
+                error!(kind = "db", block = num, error = %db_err, stopped_at = last_committed, "error saving failed block, stopping");
+                break;
+            }
+        }
+        
+        last_committed = num;
+        if num % 100 == 0 || num == to {
+            info!(block = num, to, "indexed up to block");
+        }
+    }
+    
+    Ok(last_committed)
+}
+
+
+//metodo che serve per prendere un blocco con le sue ricevute dal provider
+pub async fn fetch_block<P: EthProvider>(
+    provider: &P,
+    block_number: i64
+) -> Result<FetchedBlock, IndexerError> {
+    
+    let started = Instant::now();
+    let pipelines = config::pipelines();
+    
+    //blocco e ricevute arrivano nello stesso batch JSON-RPC
+    let (block, receipts) = provider.get_block_with_receipts(block_number).await?;
+    
+    //le tracce (per le CREATE/CREATE2 interne) richiedono debug_traceBlockByNumber,
+    //che molti provider non offrono: sono spente di default
+    let traces = if pipelines.traces {
+        provider.get_block_traces(block_number).await?
+    } else {
+        Vec::new()
+    };
+    
+    //contratti creati nel blocco, con il bytecode runtime letto alla fine del blocco
+    let mut contracts = if pipelines.contracts {
+        contracts::find_deployments(&block, &receipts, &traces)
+    } else {
+        Vec::new()
+    };
+    if !contracts.is_empty() {
+        let addresses: Vec<String> = contracts.iter().map(|c| c.address.clone()).collect();
+        let codes = provider.get_codes(&addresses, block_number).await?;
+        for (contract, code) in contracts.iter_mut().zip(codes) {
+            contract.bytecode = code;
+        }
+    }
+    
+    debug!(hash = %block.hash, duration_ms = started.elapsed().as_millis() as u64, "block fetched");
+    Ok(FetchedBlock { number: block_number, block, receipts, contracts })
+}
+
+
+//metodo che serve per prendere un blocco e salvarlo sul db. Il blocco viene scaricato prima di
+//toccare il db: con Overwrite un errore di rete lascia il blocco vecchio al suo posto
+pub async fn index_block<P: EthProvider>(
+    provider: &P,
+    db_pool: &PgPool, 
+    chain_id: i64,
+    block_number: i64,
+    mode: WriteMode
+) -> Result<(), IndexerError> {
+    async {
+        let fetched = fetch_block(provider, block_number).await?;
+        commit_block(provider, db_pool, chain_id, &fetched, mode).await
+    }
+    .instrument(info_span!("block", number = block_number, stage = "index"))
+    .await
+}
+
+
+//controllo i reorg e salvo il blocco aggiornando lo stato nella stessa transazione
+async fn commit_block<P: EthProvider>(
+    provider: &P,
+    db_pool: &PgPool, 
+    chain_id: i64,
+    fetched: &FetchedBlock,
+    mode: WriteMode
+) -> Result<(), IndexerError> {
+    
+    //fuori dal sync un rollback cancellerebbe tutta la catena sopra l'antenato, non solo l'intervallo
+    if mode != WriteMode::Sync {
+        if let Some(stale) = reorg::stale_block(provider, db_pool, chain_id, &fetched.block).await? {
+            warn!(kind = "reorg", block = stale.number, stored = %stale.stored, canonical = %stale.canonical,
+                "stored block below is not canonical, run verify to repair it");
+        }
+        return save_fetched(db_pool, chain_id, &[fetched], mode, None).await.map(|_| ());
+    }
+    
+    //prima di salvare controllo che il blocco si attacchi a quello che ho gia' sul db
+    let reorg = match reorg::find_reorg(provider, db_pool, chain_id, &fetched.block).await? {
+        Some(reorg) => reorg,
+        None => return save_fetched(db_pool, chain_id, &[fetched], mode, None).await.map(|_| ()),
+    };
+    warn!(kind = "reorg", block = reorg.stale.number, stored = %reorg.stale.stored, canonical = %reorg.stale.canonical,
+        ancestor = reorg.ancestor, "reorg detected");
+    
+    //scarico il ramo canonico fino al blocco corrente prima di toccare il db
+    let mut canonical = Vec::new();
+    for num in (reorg.ancestor + 1)..fetched.number {
+        canonical.push(
+            fetch_block(provider, num)
+                .instrument(info_span!("block", number = num, stage = "reindex"))
+                .await?
+        );
+    }
+    
+    //rollback e ramo canonico nella stessa transazione: se qualcosa fallisce il db resta sul ramo
+    //vecchio e il prossimo tentativo ritrova lo stesso reorg, senza buchi ne' stato spostato avanti
+    let blocks: Vec<&FetchedBlock> = canonical.iter().chain([fetched]).collect();
+    let deleted = save_fetched(db_pool, chain_id, &blocks, mode, Some(&reorg)).await?;
+    metrics::REORGS.inc();
+    warn!(kind = "reorg", deleted, ancestor = reorg.ancestor, depth = reorg.stale.number - reorg.ancestor,
+        "rolled back to common ancestor");
+    
+    Ok(())
+}
+
+
+//salvo i blocchi in una sola transazione, preceduti dal rollback del reorg se c'e' (solo nel sync).
+//Restituisce i blocchi cancellati dal rollback
+async fn save_fetched(
+    db_pool: &PgPool, 
+    chain_id: i64,
+    blocks: &[&FetchedBlock],
+    mode: WriteMode,
+    reorg: Option<&reorg::Reorg>
+) -> Result<u64, IndexerError> {
+    
+    let timer = metrics::DB_COMMIT_DURATION.start_timer();
+    let mut db_transazione = db_pool.begin().await?;
+    let deleted = match reorg {
+        Some(reorg) => reorg::rollback(&mut db_transazione, chain_id, reorg).await?,
+        None => 0,
+    };
+    for fetched in blocks {
+        write_block(&mut db_transazione, chain_id, fetched, mode).await?;
+    }
+    db_transazione.commit().await?;
+    
+    let duration = timer.stop_and_record();
+    debug!(duration_ms = (duration * 1000.0) as u64, "block committed");
+    metrics::BLOCKS_INDEXED.inc_by(blocks.len() as u64);
+    if let (WriteMode::Sync, Some(last)) = (mode, blocks.last()) {
+        metrics::set_last_indexed(last.number);
+    }
+    
+    Ok(deleted)
+}
+
+
+//scrivo il blocco, le transazioni, le ricevute, i log, gli eventi dei token, i contratti e (con Sync)
+//l'ultimo blocco indicizzato nella transazione di chi chiama. Con Overwrite le righe vecchie del blocco
+//vengono cancellate nella stessa transazione, quindi chi legge vede sempre o il blocco vecchio o quello nuovo
+async fn write_block(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    fetched: &FetchedBlock,
+    mode: WriteMode
+) -> Result<(), IndexerError> {
+    
+    let block_number = fetched.block.number.to_int::<i64>()?;
+    let pipelines = config::pipelines();
+    
+    if mode == WriteMode::Overwrite {
+        db::delete_block(db_transazione, chain_id, block_number).await?;
+    }
+    db::save_block(db_transazione, chain_id, &fetched.block).await?;
+    db::save_receipts(db_transazione, chain_id, &fetched.receipts).await?;
+    if pipelines.decoded_calls {
+        db::save_decoded_calls(db_transazione, chain_id, &fetched.block).await?;
+    }
+    if pipelines.tokens {
+        db::save_erc20_events(db_transazione, chain_id, &fetched.receipts).await?;
+        db::save_nft_transfers(db_transazione, chain_id, &fetched.receipts).await?;
+    }
+    if pipelines.decoded_events {
+        db::save_decoded_events(db_transazione, chain_id, &fetched.receipts).await?;
+    }
+    db::save_contracts(db_transazione, chain_id, block_number, &fetched.contracts).await?;
+    if mode == WriteMode::Sync {
+        db::update_last_indexed_block(db_transazione, chain_id, block_number).await?;
+    }
+    db::delete_failed_block(db_transazione, chain_id, block_number).await?;
+    
+    Ok(())
+}
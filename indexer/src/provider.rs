@@ -0,0 +1,605 @@
+use futures_util::{SinkExt, StreamExt};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::AbortHandle;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use crate::models::{JRPCRequest, JRPCResponse, Block, Receipt, TxTrace};
+use crate::metrics;
+use crate::quantity::Quantity;
+use crate::error::IndexerError;
+use tracing::{error, warn};
+
+//risultato di una singola richiesta dentro un batch: None se il risultato e' null
+pub type BatchItem = Result<Option<Value>, IndexerError>;
+
+//tempo massimo per un invio (connessione compresa): una richiesta appesa non deve bloccare il catch-up,
+//che salva i blocchi in ordine
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+
+//trasporto JSON-RPC verso un nodo qualsiasi (Alchemy, Infura, QuickNode, geth/reth/anvil locali).
+//le implementazioni devono solo spedire le richieste, i metodi eth_* sono costruiti sopra
+pub trait EthProvider: Send + Sync + 'static {
+    //id progressivo per riassociare le risposte
+    fn next_id(&self) -> i32;
+
+    //massimo numero di richieste in un singolo invio, i provider rifiutano batch troppo grandi
+    fn batch_size(&self) -> usize;
+
+    //invio un gruppo di richieste e restituisco le risposte cosi' come arrivano
+    fn send_batch(&self, requests: &[JRPCRequest]) -> impl Future<Output = Result<Vec<JRPCResponse<Value>>, IndexerError>> + Send;
+
+
+    // richiesta singola: restituisce None se il risultato e' null
+    fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Vec<Value>
+    ) -> impl Future<Output = Result<Option<T>, IndexerError>> + Send {
+        async move {
+            let item = self.batch(vec![(method, params)]).await?
+                .pop()
+                .ok_or_else(|| IndexerError::Transport(format!("empty response to {}", method)))?;
+
+            match item? {
+                Some(value) => Ok(Some(serde_json::from_value(value)?)),
+                None => Ok(None),
+            }
+        }
+    }
+
+
+    // invio piu' richieste insieme (a blocchi di batch_size) e riassocio le risposte
+    // tramite id. Un errore di rete fa fallire tutto, gli errori RPC restano sulla singola richiesta
+    fn batch(&self, calls: Vec<(&str, Vec<Value>)>) -> impl Future<Output = Result<Vec<BatchItem>, IndexerError>> + Send {
+        async move {
+            let mut results: Vec<BatchItem> = Vec::with_capacity(calls.len());
+            let mut calls = calls.into_iter().peekable();
+
+            while calls.peek().is_some() {
+                let requests: Vec<JRPCRequest> = calls.by_ref()
+                    .take(self.batch_size())
+                    .map(|(method, params)| JRPCRequest {
+                        jsonrpc: "2.0".to_string(),
+                        method: method.to_string(),
+                        params,
+                        id: self.next_id(),
+                    })
+                    .collect();
+
+                let started = Instant::now();
+                let sent = self.send_batch(&requests).await;
+                let elapsed = started.elapsed().as_secs_f64();
+
+                for request in &requests {
+                    metrics::RPC_REQUESTS.with_label_values(&[&request.method]).inc();
+                    metrics::RPC_DURATION.with_label_values(&[&request.method]).observe(elapsed);
+                }
+
+                let mut by_id: HashMap<i32, JRPCResponse<Value>> = match sent {
+                    Ok(responses) => responses.into_iter().map(|r| (r.id, r)).collect(),
+                    Err(e) => {
+                        for request in &requests {
+                            metrics::RPC_ERRORS.with_label_values(&[&request.method, "transport"]).inc();
+                        }
+                        return Err(e);
+                    }
+                };
+
+                for request in &requests {
+                    let item: BatchItem = match by_id.remove(&request.id) {
+                        Some(JRPCResponse { error: Some(error), .. }) => {
+                            metrics::RPC_ERRORS.with_label_values(&[&request.method, "rpc"]).inc();
+                            Err(IndexerError::from_rpc(&error))
+                        }
+                        Some(response) => Ok(response.result),
+                        None => {
+                            metrics::RPC_ERRORS.with_label_values(&[&request.method, "missing"]).inc();
+                            Err(IndexerError::Transport(format!("no response for request {} ({})", request.id, request.method)))
+                        }
+                    };
+                    results.push(item);
+                }
+            }
+
+            Ok(results)
+        }
+    }
+
+
+    // per ottenere il numero dell'ultimo blocco
+    fn get_latest_block_number(&self) -> impl Future<Output = Result<i64, IndexerError>> + Send {
+        async move {
+            let block_hex: String = self.request("eth_blockNumber", vec![])
+                .await?
+                .ok_or_else(|| IndexerError::Consistency("null result for eth_blockNumber".to_string()))?;
+
+            let latest = Quantity::from_hex(&block_hex)?.to_int::<i64>()?;
+            metrics::set_head(latest);
+            Ok(latest)
+        }
+    }
+
+
+    // solo l'hash del blocco, senza scaricare le transazioni (serve per cercare l'antenato comune)
+    fn get_block_hash(&self, block_number: i64) -> impl Future<Output = Result<String, IndexerError>> + Send {
+        async move {
+            let block_hex = format!("0x{:x}", block_number);
+
+            let header: Value = self.request("eth_getBlockByNumber", vec![json!(block_hex), json!(false)])
+                .await?
+                .ok_or_else(|| IndexerError::Consistency(format!("block {} not found on the provider", block_number)))?;
+
+            header.get("hash")
+                .and_then(|h| h.as_str())
+                .map(|h| h.to_string())
+                .ok_or_else(|| IndexerError::Decode(format!("block {} without hash", block_number)))
+        }
+    }
+
+
+    // blocco e ricevute con un solo invio, se il provider non supporta eth_getBlockReceipts
+    // chiedo le ricevute per ogni transazione (sempre in batch)
+    fn get_block_with_receipts(&self, block_number: i64) -> impl Future<Output = Result<(Block, Vec<Receipt>), IndexerError>> + Send {
+        async move {
+            let block_hex = format!("0x{:x}", block_number);
+
+            let mut results = self.batch(vec![
+                ("eth_getBlockByNumber", vec![json!(block_hex), json!(true)]),
+                ("eth_getBlockReceipts", vec![json!(block_hex)]),
+            ]).await?.into_iter();
+
+            //un blocco null vuol dire che il nodo non lo ha (ancora): puo' essere dietro agli altri nodi del provider
+            let block_value = results.next().ok_or_else(|| IndexerError::Transport("missing block in batch response".to_string()))??
+                .ok_or_else(|| IndexerError::Consistency(format!("block {} not found on the provider", block_number)))?;
+            let block: Block = serde_json::from_value(block_value).map_err(|e| {
+                error!(block = block_number, kind = "rpc_parse", error = %e, "failed to parse block from RPC response");
+                e
+            })?;
+
+            let tx_hashes: Vec<String> = block.transactions.iter().map(|tx| tx.hash.clone()).collect();
+
+            match results.next().ok_or_else(|| IndexerError::Transport("missing receipts in batch response".to_string()))? {
+                Ok(Some(value)) => match serde_json::from_value::<Vec<Receipt>>(value) {
+                    Ok(receipts) if receipts.len() == tx_hashes.len() => return Ok((block, receipts)),
+                    Ok(_) => warn!(block = block_number, "eth_getBlockReceipts incomplete, falling back to single receipts"),
+                    Err(e) => warn!(block = block_number, kind = "rpc_parse", error = %e, "eth_getBlockReceipts unparsable, falling back to single receipts"),
+                },
+                Ok(None) => warn!(block = block_number, "eth_getBlockReceipts empty, falling back to single receipts"),
+                Err(e) => warn!(block = block_number, kind = "rpc", error = %e, "eth_getBlockReceipts failed, falling back to single receipts"),
+            }
+
+            let receipts = self.get_transaction_receipts(&tx_hashes).await?;
+            Ok((block, receipts))
+        }
+    }
+
+
+    // tracce delle chiamate di tutte le transazioni del blocco (serve un nodo con le API debug_*)
+    fn get_block_traces(&self, block_number: i64) -> impl Future<Output = Result<Vec<TxTrace>, IndexerError>> + Send {
+        async move {
+            let block_hex = format!("0x{:x}", block_number);
+
+            self.request("debug_traceBlockByNumber", vec![json!(block_hex), json!({ "tracer": "callTracer" })])
+                .await?
+                .ok_or_else(|| IndexerError::Consistency(format!("no traces for block {}", block_number)))
+        }
+    }
+
+
+    // bytecode di piu' indirizzi al blocco indicato, in un solo batch
+    fn get_codes(&self, addresses: &[String], block_number: i64) -> impl Future<Output = Result<Vec<String>, IndexerError>> + Send {
+        async move {
+            let block_hex = format!("0x{:x}", block_number);
+            let calls = addresses.iter()
+                .map(|address| ("eth_getCode", vec![json!(address), json!(block_hex)]))
+                .collect();
+
+            let mut codes = Vec::with_capacity(addresses.len());
+            for item in self.batch(calls).await? {
+                let value = item?.unwrap_or(Value::String("0x".to_string()));
+                codes.push(serde_json::from_value(value)?);
+            }
+            Ok(codes)
+        }
+    }
+
+
+    // ricevute delle singole transazioni, nello stesso ordine degli hash
+    fn get_transaction_receipts(&self, tx_hashes: &[String]) -> impl Future<Output = Result<Vec<Receipt>, IndexerError>> + Send {
+        async move {
+            let calls = tx_hashes.iter()
+                .map(|hash| ("eth_getTransactionReceipt", vec![json!(hash)]))
+                .collect();
+
+            let mut receipts = Vec::with_capacity(tx_hashes.len());
+            for (hash, item) in tx_hashes.iter().zip(self.batch(calls).await?) {
+                let value = item?.ok_or_else(|| IndexerError::Consistency(format!("receipt not found for transaction {}", hash)))?;
+                receipts.push(serde_json::from_value(value)?);
+            }
+            Ok(receipts)
+        }
+    }
+}
+
+
+//----------------------------------------------------------------------------------------------------------------
+// HTTP(S): una POST per ogni batch
+pub struct HttpProvider {
+    http_client: Client,
+    url: String,
+    next_id: AtomicI32,
+    batch_size: usize,
+}
+
+impl HttpProvider {
+    pub fn new(url: String, batch_size: usize) -> Self {
+        //build fallisce solo se manca il backend TLS, come Client::new()
+        let http_client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("cannot initialize the HTTP client");
+        Self { http_client, url, next_id: AtomicI32::new(1), batch_size }
+    }
+}
+
+impl EthProvider for HttpProvider {
+    fn next_id(&self) -> i32 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    async fn send_batch(&self, requests: &[JRPCRequest]) -> Result<Vec<JRPCResponse<Value>>, IndexerError> {
+        let response = self.http_client
+            .post(&self.url)
+            .json(requests)
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.bytes().await?;
+
+        //rate limit e 5xx arrivano con un oggetto error o con una pagina qualsiasi: nel primo caso decide
+        //il codice JSON-RPC, nel secondo e' un errore di trasporto, da ritentare. Il corpo non va nel
+        //messaggio (puo' essere una pagina HTML intera)
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(json_value) if json_value.is_array() || json_value.get("error").is_some() => batch_response(json_value),
+            _ if !status.is_success() => Err(IndexerError::Transport(format!("HTTP status {}", status))),
+            Ok(json_value) => batch_response(json_value),
+            Err(e) => Err(IndexerError::Decode(format!("invalid batch response: {}", e))),
+        }
+    }
+}
+
+
+//----------------------------------------------------------------------------------------------------------------
+// connessione persistente condivisa da WS e IPC: un task scrive i batch, un altro legge le risposte e le
+// smista per id a chi le aspetta, cosi' il catch-up ha piu' batch in volo insieme (sync.concurrency)
+
+//id delle richieste di un batch in volo e canale su cui rispondere
+type PendingBatch = (Vec<i32>, oneshot::Sender<Result<Value, IndexerError>>);
+
+struct SharedConnection {
+    outgoing: mpsc::UnboundedSender<String>,
+    pending: std::sync::Mutex<Vec<PendingBatch>>,
+    closed: AtomicBool,
+    tasks: std::sync::Mutex<Vec<AbortHandle>>,
+}
+
+impl SharedConnection {
+    fn new(outgoing: mpsc::UnboundedSender<String>) -> Arc<Self> {
+        Arc::new(Self {
+            outgoing,
+            pending: std::sync::Mutex::new(Vec::new()),
+            closed: AtomicBool::new(false),
+            tasks: std::sync::Mutex::new(Vec::new()),
+        })
+    }
+
+    fn track(&self, tasks: [AbortHandle; 2]) {
+        self.tasks.lock().unwrap().extend(tasks);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    async fn send(&self, requests: &[JRPCRequest]) -> Result<Vec<JRPCResponse<Value>>, IndexerError> {
+        let (reply, response) = oneshot::channel();
+        self.pending.lock().unwrap().push((requests.iter().map(|r| r.id).collect(), reply));
+        //close segna la connessione prima di svuotare pending: se e' gia' chiusa nessuno mi rispondera'
+        if self.is_closed() || self.outgoing.send(serde_json::to_string(requests)?).is_err() {
+            return Err(IndexerError::Transport("connection closed".to_string()));
+        }
+
+        match response.await {
+            Ok(json_value) => batch_response(json_value?),
+            Err(_) => Err(IndexerError::Transport("connection closed before response".to_string())),
+        }
+    }
+
+    //risposta letta dal task di lettura: va al batch che contiene il suo id. Le notifiche (method, senza id)
+    //si ignorano; un errore senza id sull'intero batch non dice di quale si tratta, quindi va a tutti
+    fn dispatch(&self, json_value: Value) {
+        if json_value.get("method").is_some() {
+            return;
+        }
+        let id = json_value.as_array()
+            .and_then(|items| items.first())
+            .unwrap_or(&json_value)
+            .get("id")
+            .and_then(Value::as_i64);
+
+        let mut pending = self.pending.lock().unwrap();
+        match pending.iter().position(|(ids, _)| id.is_some_and(|id| ids.iter().any(|&i| i as i64 == id))) {
+            Some(position) => {
+                let _ = pending.swap_remove(position).1.send(Ok(json_value));
+            }
+            None => {
+                for (_, reply) in pending.drain(..) {
+                    let _ = reply.send(Ok(json_value.clone()));
+                }
+            }
+        }
+    }
+
+    //chiudo la connessione: i batch in attesa falliscono con un errore di trasporto (vengono ritentati)
+    //e la prossima richiesta ne apre una nuova
+    fn close(&self, reason: &str) {
+        self.closed.store(true, Ordering::SeqCst);
+        for (_, reply) in self.pending.lock().unwrap().drain(..) {
+            let _ = reply.send(Err(IndexerError::Transport(reason.to_string())));
+        }
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+//invio su una connessione condivisa, aperta con `connect` se non c'e' o se si e' chiusa.
+//il lock serve solo per aprirla, l'attesa della risposta non blocca gli altri batch
+async fn send_shared<F, Fut>(
+    slot: &Mutex<Option<Arc<SharedConnection>>>,
+    connect: F,
+    requests: &[JRPCRequest]
+) -> Result<Vec<JRPCResponse<Value>>, IndexerError> where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Arc<SharedConnection>, IndexerError>>
+{
+    let connection = with_timeout(async {
+        let mut slot = slot.lock().await;
+        match slot.as_ref().filter(|c| !c.is_closed()) {
+            Some(connection) => Ok(Arc::clone(connection)),
+            None => {
+                let connection = connect().await?;
+                *slot = Some(Arc::clone(&connection));
+                Ok(connection)
+            }
+        }
+    }).await?;
+
+    let res = with_timeout(connection.send(requests)).await;
+    if let Err(IndexerError::Transport(reason)) = &res {
+        //connessione in stato sconosciuto (anche dopo un timeout), la riapro alla prossima richiesta
+        connection.close(reason);
+    }
+    res
+}
+
+
+//----------------------------------------------------------------------------------------------------------------
+// WebSocket: una connessione persistente, riaperta alla prima richiesta dopo un errore
+pub struct WsProvider {
+    url: String,
+    connection: Mutex<Option<Arc<SharedConnection>>>,
+    next_id: AtomicI32,
+    batch_size: usize,
+}
+
+impl WsProvider {
+    pub fn new(url: String, batch_size: usize) -> Self {
+        Self { url, connection: Mutex::new(None), next_id: AtomicI32::new(1), batch_size }
+    }
+}
+
+impl EthProvider for WsProvider {
+    fn next_id(&self) -> i32 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    async fn send_batch(&self, requests: &[JRPCRequest]) -> Result<Vec<JRPCResponse<Value>>, IndexerError> {
+        send_shared(&self.connection, || ws_connect(&self.url), requests).await
+    }
+}
+
+async fn ws_connect(url: &str) -> Result<Arc<SharedConnection>, IndexerError> {
+    let (ws_stream, _) = connect_async(url).await?;
+    let (mut sink, mut stream) = ws_stream.split();
+    let (outgoing, mut queue) = mpsc::unbounded_channel::<String>();
+    let connection = SharedConnection::new(outgoing);
+
+    let writer = tokio::spawn({
+        let connection = Arc::clone(&connection);
+        async move {
+            while let Some(text) = queue.recv().await {
+                if let Err(e) = sink.send(Message::Text(text)).await {
+                    return connection.close(&IndexerError::from(e).to_string());
+                }
+            }
+        }
+    });
+
+    //i ping ricevono il pong da tungstenite durante la lettura
+    let reader = tokio::spawn({
+        let connection = Arc::clone(&connection);
+        async move {
+            let reason = loop {
+                match stream.next().await {
+                    Some(Ok(messaggio)) if messaggio.is_text() => match serde_json::from_str::<Value>(&messaggio.to_string()) {
+                        Ok(json_value) => connection.dispatch(json_value),
+                        Err(e) => break IndexerError::from(e).to_string(),
+                    },
+                    Some(Ok(messaggio)) if messaggio.is_close() => break "webSocket closed before response".to_string(),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => break IndexerError::from(e).to_string(),
+                    None => break "webSocket closed before response".to_string(),
+                }
+            };
+            connection.close(&reason);
+        }
+    });
+
+    connection.track([writer.abort_handle(), reader.abort_handle()]);
+    Ok(connection)
+}
+
+
+//----------------------------------------------------------------------------------------------------------------
+// IPC: socket unix di un nodo locale (es. geth.ipc), le risposte arrivano una dopo l'altra senza separatori.
+// Sugli altri sistemi la configurazione viene rifiutata da validate
+pub struct IpcProvider {
+    path: String,
+    connection: Mutex<Option<Arc<SharedConnection>>>,
+    next_id: AtomicI32,
+    batch_size: usize,
+}
+
+impl IpcProvider {
+    pub fn new(path: String, batch_size: usize) -> Self {
+        Self { path, connection: Mutex::new(None), next_id: AtomicI32::new(1), batch_size }
+    }
+}
+
+impl EthProvider for IpcProvider {
+    fn next_id(&self) -> i32 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    async fn send_batch(&self, requests: &[JRPCRequest]) -> Result<Vec<JRPCResponse<Value>>, IndexerError> {
+        send_shared(&self.connection, || ipc_connect(&self.path), requests).await
+    }
+}
+
+#[cfg(unix)]
+async fn ipc_connect(path: &str) -> Result<Arc<SharedConnection>, IndexerError> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (mut read_half, mut write_half) = tokio::net::UnixStream::connect(path).await?.into_split();
+    let (outgoing, mut queue) = mpsc::unbounded_channel::<String>();
+    let connection = SharedConnection::new(outgoing);
+
+    let writer = tokio::spawn({
+        let connection = Arc::clone(&connection);
+        async move {
+            while let Some(text) = queue.recv().await {
+                if let Err(e) = write_half.write_all(text.as_bytes()).await {
+                    return connection.close(&IndexerError::from(e).to_string());
+                }
+            }
+        }
+    });
+
+    //leggo a pezzi e smisto ogni JSON completo, quello a meta' resta nel buffer
+    let reader = tokio::spawn({
+        let connection = Arc::clone(&connection);
+        async move {
+            let mut buffer = Vec::new();
+            let mut chunk = [0u8; 8192];
+            let reason = 'read: loop {
+                let n = match read_half.read(&mut chunk).await {
+                    Ok(0) => break "IPC socket closed before response".to_string(),
+                    Ok(n) => n,
+                    Err(e) => break IndexerError::from(e).to_string(),
+                };
+                buffer.extend_from_slice(&chunk[..n]);
+
+                let mut values = serde_json::Deserializer::from_slice(&buffer).into_iter::<Value>();
+                for value in values.by_ref() {
+                    match value {
+                        Ok(json_value) => connection.dispatch(json_value),
+                        Err(e) if e.is_eof() => break,
+                        Err(e) => break 'read IndexerError::from(e).to_string(),
+                    }
+                }
+                let consumed = values.byte_offset();
+                buffer.drain(..consumed);
+            };
+            connection.close(&reason);
+        }
+    });
+
+    connection.track([writer.abort_handle(), reader.abort_handle()]);
+    Ok(connection)
+}
+
+#[cfg(not(unix))]
+async fn ipc_connect(path: &str) -> Result<Arc<SharedConnection>, IndexerError> {
+    Err(IndexerError::Transport(format!("IPC socket {} is only supported on unix", path)))
+}
+
+
+//la risposta a un batch e' un array. Se il nodo rifiuta il batch per intero risponde con un solo
+//oggetto error, che vale per tutte le richieste
+fn batch_response(json_value: Value) -> Result<Vec<JRPCResponse<Value>>, IndexerError> {
+    if json_value.is_array() {
+        return Ok(serde_json::from_value(json_value)?);
+    }
+    match json_value.get("error") {
+        Some(error) => Err(IndexerError::from_rpc(error)),
+        None => Err(IndexerError::Decode("batch response is neither an array nor an error".to_string())),
+    }
+}
+
+//invio con REQUEST_TIMEOUT per WS e IPC (per HTTP ci pensa il client): scaduto il tempo la connessione
+//viene chiusa dal chiamante come dopo qualsiasi altro errore di trasporto
+async fn with_timeout<T>(request: impl Future<Output = Result<T, IndexerError>>) -> Result<T, IndexerError> {
+    tokio::time::timeout(REQUEST_TIMEOUT, request)
+        .await
+        .map_err(|_| IndexerError::Transport(format!("no response within {}s", REQUEST_TIMEOUT.as_secs())))?
+}
+
+
+//----------------------------------------------------------------------------------------------------------------
+// per i provider senza webSocket: controllo l'ultimo blocco a intervalli regolari
+// e chiamo la callback con il numero in esadecimale, come fa subscribe_new_blocks
+pub async fn poll_new_blocks<P, F>(
+    provider: &P,
+    interval: Duration,
+    mut callback: F
+) -> Result<(), IndexerError> where
+    P: EthProvider,
+    F: FnMut(String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + 'static
+{
+    let mut last_seen = -1;
+
+    loop {
+        match provider.get_latest_block_number().await {
+            Ok(latest) if latest > last_seen => {
+                last_seen = latest;
+                callback(format!("0x{:x}", latest)).await;
+            }
+            Ok(_) => {}
+            Err(e) => warn!(kind = "transport", error = %e, "error polling latest block"),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
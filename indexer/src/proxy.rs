@@ -0,0 +1,484 @@
+use axum::extract::State;
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::api::ApiState;
+use crate::error::IndexerError;
+use crate::models::{JRPCRequest, JRPCResponse};
+use crate::provider::EthProvider;
+use crate::queries::{self, BlockRow, LogRow, ReceiptRow, TransactionRow};
+use crate::quantity::Quantity;
+use tracing::{error, warn};
+
+//oltre questo numero di log eth_getLogs passa al nodo, che applica i suoi limiti
+const MAX_LOGS: i64 = 10_000;
+
+
+//metodi inoltrati al provider: solo letture. Il resto (admin_*, debug_*, personal_*, miner_*, filtri
+//con stato, invio di transazioni) non deve essere raggiungibile da una porta HTTP senza autenticazione,
+//e con un provider a pagamento consumerebbe la quota della chiave
+const FORWARDED_METHODS: &[&str] = &[
+    "eth_blockNumber",
+    "eth_chainId",
+    "eth_syncing",
+    "eth_gasPrice",
+    "eth_maxPriorityFeePerGas",
+    "eth_blobBaseFee",
+    "eth_feeHistory",
+    "eth_getBalance",
+    "eth_getCode",
+    "eth_getStorageAt",
+    "eth_getTransactionCount",
+    "eth_getProof",
+    "eth_call",
+    "eth_estimateGas",
+    "eth_createAccessList",
+    "eth_getBlockByNumber",
+    "eth_getBlockByHash",
+    "eth_getBlockReceipts",
+    "eth_getBlockTransactionCountByNumber",
+    "eth_getBlockTransactionCountByHash",
+    "eth_getUncleCountByBlockNumber",
+    "eth_getUncleCountByBlockHash",
+    "eth_getUncleByBlockNumberAndIndex",
+    "eth_getUncleByBlockHashAndIndex",
+    "eth_getTransactionByHash",
+    "eth_getTransactionByBlockNumberAndIndex",
+    "eth_getTransactionByBlockHashAndIndex",
+    "eth_getTransactionReceipt",
+    "eth_getLogs",
+    "net_version",
+    "web3_clientVersion",
+];
+
+
+//richiesta del client: l'id puo' essere un numero, una stringa o null e torna indietro uguale
+#[derive(Deserialize)]
+struct ClientRequest {
+    method: String,
+    #[serde(default)]
+    params: Vec<Value>,
+    #[serde(default)]
+    id: Value,
+}
+
+
+//endpoint JSON-RPC compatibile con un nodo: blocchi, transazioni, ricevute e log vengono serviti dal db
+//quando sono indicizzati, il resto (e quello che manca sul db) viene inoltrato al provider.
+//Accetta richieste singole e batch
+pub async fn handle<P: EthProvider>(State(state): State<Arc<ApiState<P>>>, Json(body): Json<Value>) -> Json<Value> {
+    let (items, batch) = match body {
+        Value::Array(items) => (items, true),
+        other => (vec![other], false),
+    };
+    if items.is_empty() {
+        return Json(invalid_request());
+    }
+    
+    let mut responses: Vec<Value> = vec![Value::Null; items.len()];
+    let mut to_forward: Vec<(usize, ClientRequest)> = Vec::new();
+    
+    for (i, item) in items.into_iter().enumerate() {
+        let request: ClientRequest = match serde_json::from_value(item) {
+            Ok(request) => request,
+            Err(_) => {
+                responses[i] = invalid_request();
+                continue;
+            }
+        };
+        
+        if !FORWARDED_METHODS.contains(&request.method.as_str()) {
+            responses[i] = response(request.id, None, Some(rpc_error(-32601, "method not available")));
+            continue;
+        }
+        
+        match from_index(&state, &request).await {
+            Ok(Some(result)) => responses[i] = response(request.id, Some(result), None),
+            Ok(None) => to_forward.push((i, request)),
+            Err(e) => {
+                warn!(kind = e.kind(), method = %request.method, error = %e, "proxy: reading from index failed, forwarding");
+                to_forward.push((i, request));
+            }
+        }
+    }
+    
+    if !to_forward.is_empty() {
+        //al nodo le richieste partono con id nostri: quelli del client possono ripetersi o essere stringhe
+        let requests: Vec<JRPCRequest> = to_forward.iter()
+            .map(|(_, request)| JRPCRequest {
+                jsonrpc: "2.0".to_string(),
+                method: request.method.clone(),
+                params: request.params.clone(),
+                id: state.provider.next_id(),
+            })
+            .collect();
+        
+        match state.provider.send_batch(&requests).await {
+            Ok(upstream) => {
+                let mut by_id: HashMap<i32, JRPCResponse<Value>> = upstream.into_iter().map(|r| (r.id, r)).collect();
+                for ((position, client), request) in to_forward.into_iter().zip(&requests) {
+                    responses[position] = match by_id.remove(&request.id) {
+                        Some(upstream_response) => response(client.id, upstream_response.result, upstream_response.error),
+                        None => response(client.id, None, Some(rpc_error(-32603, "no response from upstream"))),
+                    };
+                }
+            }
+            Err(e) => {
+                error!(kind = e.kind(), error = %e, "proxy: upstream error");
+                for (position, client) in to_forward {
+                    responses[position] = response(client.id, None, Some(rpc_error(-32603, &format!("upstream error: {}", e))));
+                }
+            }
+        }
+    }
+    
+    if batch {
+        Json(Value::Array(responses))
+    } else {
+        Json(responses.pop().unwrap_or(Value::Null))
+    }
+}
+
+
+//come da specifica una risposta ha result oppure error, mai entrambi
+fn response(id: Value, result: Option<Value>, error: Option<Value>) -> Value {
+    match error {
+        Some(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        None => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+    }
+}
+
+fn rpc_error(code: i32, message: &str) -> Value {
+    json!({ "code": code, "message": message })
+}
+
+//richiesta che non si riesce a leggere: l'id non e' noto, quindi null come da specifica
+fn invalid_request() -> Value {
+    json!({ "jsonrpc": "2.0", "id": null, "error": rpc_error(-32600, "invalid request") })
+}
+
+
+//risultato letto dal db, None se la richiesta va inoltrata al nodo
+async fn from_index<P: EthProvider>(state: &ApiState<P>, request: &ClientRequest) -> Result<Option<Value>, IndexerError> {
+    let (db, chain_id) = (state.db.as_ref(), state.chain_id);
+    let param_str = |i: usize| request.params.get(i).and_then(Value::as_str);
+    let full_transactions = request.params.get(1).and_then(Value::as_bool).unwrap_or(false);
+    
+    match request.method.as_str() {
+        "eth_getBlockByNumber" => {
+            let number = match param_str(0).and_then(block_number) {
+                Some(number) => number,
+                None => return Ok(None),
+            };
+            match complete_block(queries::get_block_by_number(db, chain_id, number).await?) {
+                Some(block) => Ok(Some(block_json(state, block, full_transactions).await?)),
+                None => Ok(None),
+            }
+        }
+        
+        "eth_getBlockByHash" => {
+            let hash = match param_str(0) {
+                Some(hash) => hash,
+                None => return Ok(None),
+            };
+            match complete_block(queries::get_block_by_hash(db, chain_id, hash).await?) {
+                Some(block) => Ok(Some(block_json(state, block, full_transactions).await?)),
+                None => Ok(None),
+            }
+        }
+        
+        "eth_getTransactionByHash" => {
+            let tx = match param_str(0) {
+                Some(hash) => queries::get_transaction(db, chain_id, hash).await?,
+                None => return Ok(None),
+            };
+            match tx {
+                Some(tx) if block_is_complete(state, tx.block_number).await? => Ok(Some(transaction_json(&tx)?)),
+                _ => Ok(None),
+            }
+        }
+        
+        "eth_getTransactionReceipt" => {
+            let hash = match param_str(0) {
+                Some(hash) => hash,
+                None => return Ok(None),
+            };
+            let (tx, receipt) = match (
+                queries::get_transaction(db, chain_id, hash).await?,
+                queries::get_receipt(db, chain_id, hash).await?,
+            ) {
+                (Some(tx), Some(receipt)) => (tx, receipt),
+                _ => return Ok(None),
+            };
+            if !block_is_complete(state, tx.block_number).await? {
+                return Ok(None);
+            }
+            let logs = queries::get_transaction_logs(db, chain_id, hash).await?;
+            Ok(Some(receipt_json(&tx, &receipt, &logs)?))
+        }
+        
+        "eth_getLogs" => {
+            let filter = match request.params.first() {
+                Some(filter) => filter,
+                None => return Ok(None),
+            };
+            get_logs(state, filter).await
+        }
+        
+        _ => Ok(None),
+    }
+}
+
+
+//un blocco salvato prima della migrazione 0010 non ha uncles, withdrawals, firme delle transazioni e
+//logsBloom delle ricevute: un oggetto incompleto rompe le librerie client, quindi lo chiedo al nodo
+fn complete_block(block: Option<BlockRow>) -> Option<BlockRow> {
+    block.filter(|b| b.uncles.is_some())
+}
+
+async fn block_is_complete<P: EthProvider>(state: &ApiState<P>, number: i64) -> Result<bool, IndexerError> {
+    Ok(complete_block(queries::get_block_by_number(&state.db, state.chain_id, number).await?).is_some())
+}
+
+
+//numero di blocco dal parametro: solo numeri espliciti ed "earliest". I tag come latest, safe
+//e finalized dipendono dalla testa della catena, quindi li risolve il nodo
+fn block_number(tag: &str) -> Option<i64> {
+    match tag {
+        "earliest" => Some(0),
+        t if t.starts_with("0x") => Quantity::from_hex(t).and_then(|q| q.to_int::<i64>()).ok(),
+        _ => None,
+    }
+}
+
+
+//servo eth_getLogs solo se tutti i blocchi dell'intervallo sono sul db, altrimenti il risultato
+//sarebbe incompleto
+async fn get_logs<P: EthProvider>(state: &ApiState<P>, filter: &Value) -> Result<Option<Value>, IndexerError> {
+    let (db, chain_id) = (state.db.as_ref(), state.chain_id);
+    
+    let (from, to) = match filter.get("blockHash").and_then(Value::as_str) {
+        Some(hash) => match queries::get_block_by_hash(db, chain_id, hash).await? {
+            Some(block) => (block.number, block.number),
+            None => return Ok(None),
+        },
+        None => {
+            //senza fromBlock/toBlock il default e' latest, che decide il nodo
+            let from = filter.get("fromBlock").and_then(Value::as_str).and_then(block_number);
+            let to = filter.get("toBlock").and_then(Value::as_str).and_then(block_number);
+            match (from, to) {
+                (Some(from), Some(to)) if from <= to => (from, to),
+                _ => return Ok(None),
+            }
+        }
+    };
+    
+    if queries::count_blocks(db, chain_id, from, to).await? != to - from + 1 {
+        return Ok(None);
+    }
+    
+    let addresses: Vec<String> = match filter.get("address") {
+        Some(Value::String(address)) => vec![address.clone()],
+        Some(Value::Array(addresses)) => addresses.iter().filter_map(|a| a.as_str().map(String::from)).collect(),
+        _ => Vec::new(),
+    };
+    
+    //ogni posizione puo' essere null (qualsiasi), un topic o una lista di alternative
+    let topics: Vec<Vec<String>> = match filter.get("topics") {
+        Some(Value::Array(positions)) => positions.iter()
+            .map(|position| match position {
+                Value::String(topic) => vec![topic.clone()],
+                Value::Array(options) => options.iter().filter_map(|t| t.as_str().map(String::from)).collect(),
+                _ => Vec::new(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    
+    let logs = queries::get_logs(db, chain_id, from, to, &addresses, &topics, MAX_LOGS + 1).await?;
+    if logs.len() as i64 > MAX_LOGS {
+        return Ok(None);
+    }
+    
+    Ok(Some(Value::Array(logs.iter().map(log_json).collect())))
+}
+
+
+//oggetti nel formato del nodo (quantita' in esadecimale, camelCase). I campi che il nodo non
+//restituisce per quel blocco o tipo di transazione mancano del tutto, come nelle sue risposte
+fn quantity(n: i64) -> Value {
+    Value::String(format!("{:#x}", n))
+}
+
+fn optional_quantity(decimal: &Option<String>) -> Result<Option<Value>, IndexerError> {
+    Ok(match decimal {
+        Some(d) => Some(Value::String(Quantity::from_decimal(d)?.to_hex())),
+        None => None,
+    })
+}
+
+async fn block_json<P: EthProvider>(state: &ApiState<P>, block: BlockRow, full_transactions: bool) -> Result<Value, IndexerError> {
+    let transactions = queries::get_block_transactions(&state.db, state.chain_id, block.number).await?;
+    let transactions = if full_transactions {
+        transactions.iter().map(transaction_json).collect::<Result<Vec<Value>, IndexerError>>()?
+    } else {
+        transactions.into_iter().map(|tx| Value::String(tx.hash)).collect()
+    };
+    
+    let mut object = Map::new();
+    object.insert("number".into(), quantity(block.number));
+    object.insert("hash".into(), json!(block.hash));
+    object.insert("parentHash".into(), json!(block.parent_hash));
+    object.insert("timestamp".into(), quantity(block.timestamp));
+    object.insert("miner".into(), json!(block.miner));
+    object.insert("gasUsed".into(), quantity(block.gas_used));
+    object.insert("gasLimit".into(), quantity(block.gas_limit));
+    object.insert("size".into(), quantity(block.size));
+    object.insert("transactions".into(), Value::Array(transactions));
+    object.insert("uncles".into(), block.uncles.unwrap_or_else(|| json!([])));
+    
+    for (key, value) in [
+        ("difficulty", &block.difficulty),
+        ("totalDifficulty", &block.total_difficulty),
+        ("baseFeePerGas", &block.base_fee_per_gas),
+    ] {
+        if let Some(value) = optional_quantity(value)? {
+            object.insert(key.into(), value);
+        }
+    }
+    for (key, value) in [
+        ("sha3Uncles", block.sha3_uncles),
+        ("stateRoot", block.state_root),
+        ("transactionsRoot", block.transactions_root),
+        ("receiptsRoot", block.receipts_root),
+        ("logsBloom", block.logs_bloom),
+        ("extraData", block.extra_data),
+        ("mixHash", block.mix_hash),
+        ("nonce", block.nonce),
+        ("withdrawalsRoot", block.withdrawals_root),
+        ("parentBeaconBlockRoot", block.parent_beacon_block_root),
+        ("requestsHash", block.requests_hash),
+    ] {
+        if let Some(value) = value {
+            object.insert(key.into(), Value::String(value));
+        }
+    }
+    for (key, value) in [("blobGasUsed", block.blob_gas_used), ("excessBlobGas", block.excess_blob_gas)] {
+        if let Some(value) = value {
+            object.insert(key.into(), quantity(value));
+        }
+    }
+    if let Some(withdrawals) = block.withdrawals {
+        object.insert("withdrawals".into(), withdrawals);
+    }
+    
+    Ok(Value::Object(object))
+}
+
+fn transaction_json(tx: &TransactionRow) -> Result<Value, IndexerError> {
+    let mut object = Map::new();
+    object.insert("hash".into(), json!(tx.hash));
+    object.insert("blockHash".into(), json!(tx.block_hash));
+    object.insert("blockNumber".into(), quantity(tx.block_number));
+    object.insert("transactionIndex".into(), quantity(tx.transaction_index as i64));
+    object.insert("from".into(), json!(tx.from_address));
+    object.insert("to".into(), json!(tx.to_address));
+    object.insert("value".into(), Value::String(Quantity::from_decimal(&tx.value)?.to_hex()));
+    object.insert("nonce".into(), quantity(tx.nonce));
+    object.insert("gas".into(), quantity(tx.gas));
+    object.insert("input".into(), json!(tx.input));
+    
+    for (key, value) in [
+        ("gasPrice", &tx.gas_price),
+        ("maxFeePerGas", &tx.max_fee_per_gas),
+        ("maxPriorityFeePerGas", &tx.max_priority_fee_per_gas),
+        ("maxFeePerBlobGas", &tx.max_fee_per_blob_gas),
+        ("v", &tx.v),
+        ("r", &tx.r),
+        ("s", &tx.s),
+    ] {
+        if let Some(value) = optional_quantity(value)? {
+            object.insert(key.into(), value);
+        }
+    }
+    for (key, value) in [
+        ("type", tx.tx_type.map(i64::from)),
+        ("chainId", tx.tx_chain_id),
+        ("yParity", tx.y_parity.map(i64::from)),
+    ] {
+        if let Some(value) = value {
+            object.insert(key.into(), quantity(value));
+        }
+    }
+    for (key, value) in [
+        ("accessList", &tx.access_list),
+        ("blobVersionedHashes", &tx.blob_versioned_hashes),
+        ("authorizationList", &tx.authorization_list),
+    ] {
+        if let Some(value) = value {
+            object.insert(key.into(), value.clone());
+        }
+    }
+    
+    Ok(Value::Object(object))
+}
+
+fn receipt_json(tx: &TransactionRow, receipt: &ReceiptRow, logs: &[LogRow]) -> Result<Value, IndexerError> {
+    let mut object = Map::new();
+    object.insert("transactionHash".into(), json!(receipt.transaction_hash));
+    object.insert("transactionIndex".into(), quantity(tx.transaction_index as i64));
+    object.insert("blockHash".into(), json!(tx.block_hash));
+    object.insert("blockNumber".into(), quantity(receipt.block_number));
+    object.insert("from".into(), json!(tx.from_address));
+    object.insert("to".into(), json!(tx.to_address));
+    object.insert("gasUsed".into(), quantity(receipt.gas_used));
+    object.insert("cumulativeGasUsed".into(), quantity(receipt.cumulative_gas_used));
+    object.insert("contractAddress".into(), json!(receipt.contract_address));
+    object.insert("logs".into(), Value::Array(logs.iter().map(log_json).collect()));
+    
+    for (key, value) in [
+        ("effectiveGasPrice", &receipt.effective_gas_price),
+        ("blobGasPrice", &receipt.blob_gas_price),
+    ] {
+        if let Some(value) = optional_quantity(value)? {
+            object.insert(key.into(), value);
+        }
+    }
+    for (key, value) in [
+        ("status", receipt.status.map(i64::from)),
+        ("type", tx.tx_type.map(i64::from)),
+        ("blobGasUsed", receipt.blob_gas_used),
+    ] {
+        if let Some(value) = value {
+            object.insert(key.into(), quantity(value));
+        }
+    }
+    for (key, value) in [("logsBloom", &receipt.logs_bloom), ("root", &receipt.root)] {
+        if let Some(value) = value {
+            object.insert(key.into(), json!(value));
+        }
+    }
+    
+    Ok(Value::Object(object))
+}
+
+fn log_json(log: &LogRow) -> Value {
+    let topics: Vec<&String> = [&log.topic0, &log.topic1, &log.topic2, &log.topic3]
+        .into_iter()
+        .flatten()
+        .collect();
+    
+    json!({
+        "address": log.address,
+        "topics": topics,
+        "data": log.data,
+        "blockNumber": quantity(log.block_number),
+        "blockHash": log.block_hash,
+        "transactionHash": log.transaction_hash,
+        "transactionIndex": log.transaction_index.map(|i| quantity(i as i64)),
+        "logIndex": quantity(log.log_index as i64),
+        "removed": false,
+    })
+}
@@ -0,0 +1,155 @@
+use ethabi::Uint;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use crate::error::IndexerError;
+
+//quantita' JSON-RPC (numeri di blocco, gas, wei, nonce...): intero senza segno fino a 256 bit,
+//in JSON "0x" + cifre esadecimali senza zeri iniziali. Sul db va in NUMERIC(78,0) come stringa
+//decimale, oppure in BIGINT con to_int quando il valore e' per forza piccolo (numeri, indici)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Quantity(Uint);
+
+
+impl Quantity {
+    //parsing stretto come quello dei nodi: niente "0x" vuoto, niente zeri iniziali ("0x0" e' lo zero),
+    //al massimo 64 cifre
+    pub fn from_hex(hex: &str) -> Result<Self, IndexerError> {
+        let digits = hex.strip_prefix("0x")
+            .ok_or_else(|| IndexerError::Decode(format!("quantity without 0x prefix: {}", hex)))?;
+
+        if digits.is_empty() {
+            return Err(IndexerError::Decode("empty quantity 0x".to_string()));
+        }
+        if digits.len() > 1 && digits.starts_with('0') {
+            return Err(IndexerError::Decode(format!("quantity with leading zeros: {}", hex)));
+        }
+        if digits.len() > 64 {
+            return Err(IndexerError::Decode(format!("quantity larger than 256 bits: {}", hex)));
+        }
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(IndexerError::Decode(format!("invalid hex quantity: {}", hex)));
+        }
+
+        Uint::from_str_radix(digits, 16)
+            .map(Quantity)
+            .map_err(|e| IndexerError::Decode(format!("invalid hex quantity {}: {:?}", hex, e)))
+    }
+
+    //parola ABI da 32 byte (data dei log, topic): sempre 64 cifre con gli zeri iniziali, "0x" facoltativo
+    pub fn from_abi_word(word: &str) -> Result<Self, IndexerError> {
+        let digits = word.strip_prefix("0x").unwrap_or(word);
+        if digits.len() != 64 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(IndexerError::Decode(format!("invalid 32 byte ABI word: {}", word)));
+        }
+
+        Uint::from_str_radix(digits, 16)
+            .map(Quantity)
+            .map_err(|e| IndexerError::Decode(format!("invalid 32 byte ABI word {}: {:?}", word, e)))
+    }
+
+    //stringa decimale, come arriva da una colonna NUMERIC letta con ::TEXT
+    pub fn from_decimal(decimal: &str) -> Result<Self, IndexerError> {
+        Uint::from_dec_str(decimal)
+            .map(Quantity)
+            .map_err(|e| IndexerError::Decode(format!("invalid decimal value {}: {:?}", decimal, e)))
+    }
+
+    //valore da salvare in una colonna NUMERIC(78,0) (bind come testo con $n::NUMERIC)
+    pub fn to_decimal(self) -> String {
+        self.0.to_string()
+    }
+
+    //forma JSON-RPC, "0x0" per lo zero
+    pub fn to_hex(self) -> String {
+        format!("{:#x}", self.0)
+    }
+
+    //conversione controllata per le colonne intere: errore invece di un valore troncato
+    pub fn to_int<T: TryFrom<u64>>(self) -> Result<T, IndexerError> {
+        if self.0.bits() > 64 {
+            return Err(IndexerError::Decode(format!("quantity {} out of range", self.to_hex())));
+        }
+        T::try_from(self.0.as_u64())
+            .map_err(|_| IndexerError::Decode(format!("quantity {} out of range", self.to_hex())))
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        //solo il messaggio: serde_json lo riporta gia' come errore di decodifica
+        Quantity::from_hex(&hex).map_err(|e| match e {
+            IndexerError::Decode(message) => serde::de::Error::custom(message),
+            e => serde::de::Error::custom(e),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_hex_accepts_canonical_quantities() {
+        assert_eq!(Quantity::from_hex("0x0").unwrap().to_decimal(), "0");
+        assert_eq!(Quantity::from_hex("0x1b4").unwrap().to_decimal(), "436");
+        assert_eq!(Quantity::from_hex("0xABC").unwrap().to_hex(), "0xabc");
+
+        let max = format!("0x{}", "f".repeat(64));
+        assert_eq!(Quantity::from_hex(&max).unwrap().to_hex(), max);
+    }
+
+    #[test]
+    fn from_hex_rejects_non_canonical_quantities() {
+        for hex in ["0x", "", "1b4", "0x01b4", "0x00", "0xg1", "0x 1", "-0x1"] {
+            assert!(Quantity::from_hex(hex).is_err(), "{} accepted", hex);
+        }
+        //65 cifre: oltre i 256 bit
+        assert!(Quantity::from_hex(&format!("0x1{}", "0".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn to_int_rejects_overflow() {
+        assert_eq!(Quantity::from_hex("0x7fffffffffffffff").unwrap().to_int::<i64>().unwrap(), i64::MAX);
+        assert!(Quantity::from_hex("0x8000000000000000").unwrap().to_int::<i64>().is_err());
+        assert!(Quantity::from_hex("0xffffffffffffffff").unwrap().to_int::<i64>().is_err());
+        assert_eq!(Quantity::from_hex("0xffffffffffffffff").unwrap().to_int::<u64>().unwrap(), u64::MAX);
+        assert!(Quantity::from_hex("0x10000000000000000").unwrap().to_int::<u64>().is_err());
+        assert!(Quantity::from_hex("0x100").unwrap().to_int::<u8>().is_err());
+    }
+
+    #[test]
+    fn from_abi_word_requires_32_bytes() {
+        let word = format!("{:064x}", 12345);
+        assert_eq!(Quantity::from_abi_word(&word).unwrap().to_decimal(), "12345");
+        assert_eq!(Quantity::from_abi_word(&format!("0x{}", word)).unwrap().to_decimal(), "12345");
+        assert_eq!(
+            Quantity::from_abi_word(&"f".repeat(64)).unwrap().to_decimal(),
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+        );
+
+        for word in ["0x", "", "0x1", &"0".repeat(63), &"0".repeat(65), &format!("{}g", "0".repeat(63))] {
+            assert!(Quantity::from_abi_word(word).is_err(), "{} accepted", word);
+        }
+    }
+
+    #[test]
+    fn decimal_round_trip() {
+        let value = Quantity::from_decimal("1606938044258990275541962092341162602522202993782792835301376").unwrap();
+        assert_eq!(value.to_hex(), format!("0x1{}", "0".repeat(50)));
+        assert!(Quantity::from_decimal("0x10").is_err());
+        assert!(Quantity::from_decimal("-1").is_err());
+    }
+}
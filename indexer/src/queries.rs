@@ -0,0 +1,279 @@
+use serde::Serialize;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use crate::error::IndexerError;
+
+//letture per le API (REST, GraphQL, proxy JSON-RPC). Le scritture restano in db.rs.
+//le colonne NUMERIC tornano come stringhe decimali, in JSON un uint256 non sta in un numero
+
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct BlockRow {
+    pub number: i64,
+    pub hash: String,
+    pub parent_hash: String,
+    pub timestamp: i64,
+    pub miner: String,
+    pub gas_used: i64,
+    pub gas_limit: i64,
+    pub transactions_count: i32,
+    pub size: i64,
+    pub difficulty: Option<String>,
+    pub total_difficulty: Option<String>,
+    pub state_root: Option<String>,
+    pub transactions_root: Option<String>,
+    pub receipts_root: Option<String>,
+    pub logs_bloom: Option<String>,
+    pub extra_data: Option<String>,
+    pub mix_hash: Option<String>,
+    pub nonce: Option<String>,
+    pub base_fee_per_gas: Option<String>,
+    pub withdrawals_root: Option<String>,
+    pub blob_gas_used: Option<i64>,
+    pub excess_blob_gas: Option<i64>,
+    pub parent_beacon_block_root: Option<String>,
+    pub requests_hash: Option<String>,
+    pub sha3_uncles: Option<String>,
+    //NULL per i blocchi salvati prima della migrazione 0010 (mancano i campi per il proxy)
+    pub uncles: Option<serde_json::Value>,
+    pub withdrawals: Option<serde_json::Value>,
+}
+
+const BLOCK_COLUMNS: &str = "number, hash, parent_hash, timestamp, miner, gas_used, gas_limit, transactions_count, size,
+    difficulty::TEXT AS difficulty, total_difficulty::TEXT AS total_difficulty, state_root, transactions_root,
+    receipts_root, logs_bloom, extra_data, mix_hash, nonce, base_fee_per_gas::TEXT AS base_fee_per_gas,
+    withdrawals_root, blob_gas_used, excess_blob_gas, parent_beacon_block_root, requests_hash,
+    sha3_uncles, uncles, withdrawals";
+
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TransactionRow {
+    pub hash: String,
+    pub block_number: i64,
+    pub block_hash: Option<String>,
+    pub transaction_index: i32,
+    pub from_address: String,
+    pub to_address: Option<String>,
+    pub value: String,
+    pub nonce: i64,
+    pub gas: i64,
+    pub gas_price: Option<String>,
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+    pub input: String,
+    pub tx_type: Option<i16>,
+    pub tx_chain_id: Option<i64>,
+    pub method_name: Option<String>,
+    pub method_signature: Option<String>,
+    pub method_args: Option<serde_json::Value>,
+    pub v: Option<String>,
+    pub r: Option<String>,
+    pub s: Option<String>,
+    pub y_parity: Option<i16>,
+    pub access_list: Option<serde_json::Value>,
+    pub max_fee_per_blob_gas: Option<String>,
+    pub blob_versioned_hashes: Option<serde_json::Value>,
+    pub authorization_list: Option<serde_json::Value>,
+}
+
+const TRANSACTION_COLUMNS: &str = "hash, block_number,
+    (SELECT b.hash FROM blocks b WHERE b.chain_id = transactions.chain_id AND b.number = transactions.block_number) AS block_hash,
+    transaction_index, from_address, to_address, value::TEXT AS value,
+    nonce, gas, gas_price::TEXT AS gas_price, max_fee_per_gas::TEXT AS max_fee_per_gas,
+    max_priority_fee_per_gas::TEXT AS max_priority_fee_per_gas, input, tx_type, tx_chain_id,
+    method_name, method_signature, method_args, v::TEXT AS v, r::TEXT AS r, s::TEXT AS s, y_parity,
+    access_list, max_fee_per_blob_gas::TEXT AS max_fee_per_blob_gas, blob_versioned_hashes, authorization_list";
+
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReceiptRow {
+    pub transaction_hash: String,
+    pub block_number: i64,
+    pub status: Option<i16>,
+    pub gas_used: i64,
+    pub cumulative_gas_used: i64,
+    pub effective_gas_price: Option<String>,
+    pub contract_address: Option<String>,
+    pub logs_bloom: Option<String>,
+    pub root: Option<String>,
+    pub blob_gas_used: Option<i64>,
+    pub blob_gas_price: Option<String>,
+}
+
+const RECEIPT_COLUMNS: &str = "transaction_hash, block_number, status, gas_used, cumulative_gas_used,
+    effective_gas_price::TEXT AS effective_gas_price, contract_address, logs_bloom, root, blob_gas_used,
+    blob_gas_price::TEXT AS blob_gas_price";
+
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct LogRow {
+    pub block_number: i64,
+    pub block_hash: Option<String>,
+    pub log_index: i32,
+    pub transaction_hash: String,
+    pub transaction_index: Option<i32>,
+    pub address: String,
+    pub topic0: Option<String>,
+    pub topic1: Option<String>,
+    pub topic2: Option<String>,
+    pub topic3: Option<String>,
+    pub data: String,
+}
+
+const LOG_COLUMNS: &str = "block_number,
+    (SELECT b.hash FROM blocks b WHERE b.chain_id = logs.chain_id AND b.number = logs.block_number) AS block_hash,
+    log_index, transaction_hash,
+    (SELECT t.transaction_index FROM transactions t WHERE t.chain_id = logs.chain_id AND t.hash = logs.transaction_hash) AS transaction_index,
+    address, topic0, topic1, topic2, topic3, data";
+
+
+pub async fn get_block_by_number(pool: &PgPool, chain_id: i64, number: i64) -> Result<Option<BlockRow>, IndexerError> {
+    Ok(sqlx::query_as(&format!("SELECT {} FROM blocks WHERE chain_id = $1 AND number = $2", BLOCK_COLUMNS))
+        .bind(chain_id)
+        .bind(number)
+        .fetch_optional(pool)
+        .await?)
+}
+
+pub async fn get_block_by_hash(pool: &PgPool, chain_id: i64, hash: &str) -> Result<Option<BlockRow>, IndexerError> {
+    Ok(sqlx::query_as(&format!("SELECT {} FROM blocks WHERE chain_id = $1 AND hash = $2", BLOCK_COLUMNS))
+        .bind(chain_id)
+        .bind(hash.to_lowercase())
+        .fetch_optional(pool)
+        .await?)
+}
+
+//blocchi da `from` a `to` compresi, in ordine crescente, al massimo `limit`
+pub async fn get_blocks(pool: &PgPool, chain_id: i64, from: i64, to: i64, limit: i64) -> Result<Vec<BlockRow>, IndexerError> {
+    Ok(sqlx::query_as(&format!(
+        "SELECT {} FROM blocks WHERE chain_id = $1 AND number BETWEEN $2 AND $3 ORDER BY number LIMIT $4",
+        BLOCK_COLUMNS
+    ))
+    .bind(chain_id)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_block_transactions(pool: &PgPool, chain_id: i64, number: i64) -> Result<Vec<TransactionRow>, IndexerError> {
+    Ok(sqlx::query_as(&format!(
+        "SELECT {} FROM transactions WHERE chain_id = $1 AND block_number = $2 ORDER BY transaction_index",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(chain_id)
+    .bind(number)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_transaction(pool: &PgPool, chain_id: i64, hash: &str) -> Result<Option<TransactionRow>, IndexerError> {
+    Ok(sqlx::query_as(&format!("SELECT {} FROM transactions WHERE chain_id = $1 AND hash = $2", TRANSACTION_COLUMNS))
+        .bind(chain_id)
+        .bind(hash.to_lowercase())
+        .fetch_optional(pool)
+        .await?)
+}
+
+pub async fn get_receipt(pool: &PgPool, chain_id: i64, hash: &str) -> Result<Option<ReceiptRow>, IndexerError> {
+    Ok(sqlx::query_as(&format!("SELECT {} FROM receipts WHERE chain_id = $1 AND transaction_hash = $2", RECEIPT_COLUMNS))
+        .bind(chain_id)
+        .bind(hash.to_lowercase())
+        .fetch_optional(pool)
+        .await?)
+}
+
+pub async fn get_transaction_logs(pool: &PgPool, chain_id: i64, hash: &str) -> Result<Vec<LogRow>, IndexerError> {
+    Ok(sqlx::query_as(&format!(
+        "SELECT {} FROM logs WHERE chain_id = $1 AND transaction_hash = $2 ORDER BY log_index",
+        LOG_COLUMNS
+    ))
+    .bind(chain_id)
+    .bind(hash.to_lowercase())
+    .fetch_all(pool)
+    .await?)
+}
+
+//transazioni inviate o ricevute da un indirizzo, dalla piu' recente. `before` e' la posizione
+//(blocco, indice) dell'ultima transazione della pagina precedente
+pub async fn get_address_transactions(
+    pool: &PgPool,
+    chain_id: i64,
+    address: &str,
+    before: Option<(i64, i32)>,
+    limit: i64
+) -> Result<Vec<TransactionRow>, IndexerError> {
+    let (before_block, before_index) = before.unwrap_or((i64::MAX, i32::MAX));
+    
+    Ok(sqlx::query_as(&format!(
+        "SELECT {} FROM transactions
+         WHERE chain_id = $1 AND (from_address = $2 OR to_address = $2)
+           AND (block_number, transaction_index) < ($3, $4)
+         ORDER BY block_number DESC, transaction_index DESC
+         LIMIT $5",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(chain_id)
+    .bind(address.to_lowercase())
+    .bind(before_block)
+    .bind(before_index)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_block_transaction_at(pool: &PgPool, chain_id: i64, number: i64, index: i32) -> Result<Option<TransactionRow>, IndexerError> {
+    Ok(sqlx::query_as(&format!(
+        "SELECT {} FROM transactions WHERE chain_id = $1 AND block_number = $2 AND transaction_index = $3",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(chain_id)
+    .bind(number)
+    .bind(index)
+    .fetch_optional(pool)
+    .await?)
+}
+
+//log filtrati come eth_getLogs: intervallo di blocchi, indirizzi in OR e topic per posizione
+//(ogni posizione e' una lista di alternative, vuota = qualsiasi valore)
+pub async fn get_logs(
+    pool: &PgPool,
+    chain_id: i64,
+    from: i64,
+    to: i64,
+    addresses: &[String],
+    topics: &[Vec<String>],
+    limit: i64
+) -> Result<Vec<LogRow>, IndexerError> {
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::new(format!("SELECT {} FROM logs WHERE chain_id = ", LOG_COLUMNS));
+    query.push_bind(chain_id);
+    query.push(" AND block_number BETWEEN ").push_bind(from).push(" AND ").push_bind(to);
+    
+    if !addresses.is_empty() {
+        let addresses: Vec<String> = addresses.iter().map(|a| a.to_lowercase()).collect();
+        query.push(" AND address = ANY(").push_bind(addresses).push(")");
+    }
+    
+    for (position, options) in topics.iter().enumerate().take(4) {
+        if !options.is_empty() {
+            let options: Vec<String> = options.iter().map(|t| t.to_lowercase()).collect();
+            query.push(format!(" AND topic{} = ANY(", position)).push_bind(options).push(")");
+        }
+    }
+    
+    query.push(" ORDER BY block_number, log_index LIMIT ").push_bind(limit);
+    
+    Ok(query.build_query_as().fetch_all(pool).await?)
+}
+
+//quanti blocchi ci sono sul db tra `from` e `to`: se mancano dei blocchi i risultati sarebbero incompleti
+pub async fn count_blocks(pool: &PgPool, chain_id: i64, from: i64, to: i64) -> Result<i64, IndexerError> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM blocks WHERE chain_id = $1 AND number BETWEEN $2 AND $3")
+        .bind(chain_id)
+        .bind(from)
+        .bind(to)
+        .fetch_one(pool)
+        .await?;
+    
+    Ok(count)
+}
@@ -0,0 +1,108 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use crate::provider::EthProvider;
+use crate::models::Block;
+use crate::db;
+use crate::error::IndexerError;
+
+//oltre questa profondita' non cerco l'antenato comune, meglio fermarsi e controllare a mano
+const MAX_REORG_DEPTH: i64 = 128;
+
+
+//blocco salvato che non sta sulla catena del blocco in arrivo
+pub struct StaleBlock {
+    pub number: i64,
+    pub stored: String,
+    pub canonical: String,
+}
+
+//reorg trovato ma non ancora applicato: i blocchi sopra `ancestor` sul db sono del ramo orfano
+pub struct Reorg {
+    pub stale: StaleBlock,
+    pub ancestor: i64,
+}
+
+
+//confronto il blocco in arrivo con il blocco salvato piu' vicino sotto di lui. Di solito e' il genitore
+//e basta il parent hash; se il genitore manca (blocco finito in failed_blocks, buco) confronto
+//il blocco salvato con l'hash canonico chiesto al nodo, cosi' il controllo non salta.
+//None se il blocco si attacca alla catena salvata o se sotto non c'e' niente
+pub async fn stale_block<P: EthProvider>(
+    provider: &P,
+    db_pool: &PgPool,
+    chain_id: i64,
+    block: &Block
+) -> Result<Option<StaleBlock>, IndexerError> {
+    let block_number = block.number.to_int::<i64>()?;
+    
+    let (number, stored) = match db::get_block_below(db_pool, chain_id, block_number).await? {
+        Some(stored) => stored,
+        None => return Ok(None),
+    };
+    
+    let canonical = if number == block_number - 1 {
+        block.parent_hash.clone()
+    } else {
+        provider.get_block_hash(number).await?
+    };
+    
+    Ok((stored != canonical).then_some(StaleBlock { number, stored, canonical }))
+}
+
+
+//cerca un reorg senza toccare il db: se il blocco non si attacca alla catena salvata torna indietro
+//finche' l'hash salvato coincide con quello canonico. Il rollback lo fa chi chiama con rollback,
+//nella stessa transazione in cui salva il ramo canonico
+pub async fn find_reorg<P: EthProvider>(
+    provider: &P,
+    db_pool: &PgPool,
+    chain_id: i64,
+    block: &Block
+) -> Result<Option<Reorg>, IndexerError> {
+    
+    let stale = match stale_block(provider, db_pool, chain_id, block).await? {
+        Some(stale) => stale,
+        None => return Ok(None),
+    };
+    
+    let mut ancestor = stale.number - 1;
+    loop {
+        if stale.number - ancestor > MAX_REORG_DEPTH {
+            return Err(IndexerError::DeepReorg(format!("reorg deeper than {} blocks at block {}", MAX_REORG_DEPTH, stale.number)));
+        }
+        
+        let stored = match db::get_block_hash(db_pool, chain_id, ancestor).await? {
+            Some(hash) => hash,
+            //buco nel db: considero questo punto come antenato
+            None => break,
+        };
+        
+        let canonical = provider.get_block_hash(ancestor).await?;
+        if stored == canonical {
+            break;
+        }
+        ancestor -= 1;
+    }
+    
+    Ok(Some(Reorg { stale, ancestor }))
+}
+
+
+//cancella il ramo orfano e registra il reorg. Restituisce i blocchi cancellati
+pub async fn rollback(
+    db_transazione: &mut Transaction<'_, Postgres>,
+    chain_id: i64,
+    reorg: &Reorg
+) -> Result<u64, IndexerError> {
+    let deleted = db::rollback_to(db_transazione, chain_id, reorg.ancestor).await?;
+    db::save_reorg(
+        db_transazione,
+        chain_id,
+        reorg.stale.number,
+        &reorg.stale.stored,
+        &reorg.stale.canonical,
+        reorg.ancestor,
+        reorg.stale.number - reorg.ancestor
+    ).await?;
+    
+    Ok(deleted)
+}
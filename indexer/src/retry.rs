@@ -0,0 +1,93 @@
+use sqlx::PgPool;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::provider::EthProvider;
+use crate::{db, pipeline};
+use crate::pipeline::WriteMode;
+use crate::error::IndexerError;
+use tracing::{error, info, warn};
+
+
+//quante volte ritentare e quanto aspettare tra un tentativo e l'altro
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    //backoff esponenziale con jitter: un valore a caso tra meta' e tutto il ritardo,
+    //cosi' i worker che falliscono insieme non ritentano tutti nello stesso istante
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(1 << attempt.min(16)).min(self.max_delay);
+        let half = exp / 2;
+        let jitter = random_u64() % (half.as_millis() as u64 + 1);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+
+//numero casuale senza dipendenze esterne: RandomState ha chiavi diverse ogni volta
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+
+//eseguo op finche' non riesce o finiscono i tentativi, restituisco l'ultimo errore.
+//gli errori non transitori (formato sbagliato, vincolo violato...) tornano subito senza ritentare
+pub async fn with_backoff<T, F, Fut>(
+    policy: &RetryPolicy,
+    what: &str,
+    mut op: F
+) -> Result<T, IndexerError> where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, IndexerError>>
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(e) if e.is_transient() && attempt + 1 < policy.attempts => {
+                let delay = policy.delay(attempt);
+                warn!(what, kind = e.kind(), attempt = attempt + 1, attempts = policy.attempts, error = %e, retry_in_ms = delay.as_millis() as u64, "operation failed, retrying");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+
+//task in background: riprova i blocchi finiti in failed_blocks quando e' il loro turno.
+//se riesce la riga viene cancellata dal salvataggio, altrimenti viene ripianificata piu' avanti
+pub async fn drain_failed_blocks<P: EthProvider>(
+    provider: Arc<P>,
+    db_pool: Arc<PgPool>,
+    chain_id: i64,
+    interval: Duration
+) {
+    loop {
+        match db::get_due_failed_blocks(&db_pool, chain_id, 50).await {
+            Ok(due) => {
+                for block_num in due {
+                    match pipeline::index_block(provider.as_ref(), &db_pool, chain_id, block_num, WriteMode::Insert).await {
+                        Ok(_) => info!(block = block_num, "failed block indexed on retry"),
+                        Err(e) => {
+                            warn!(block = block_num, kind = e.kind(), error = %e, "failed block still failing");
+                            if let Err(e) = db::save_failed_block(&db_pool, chain_id, block_num, &e.to_string(), 1).await {
+                                error!(kind = "db", block = block_num, error = %e, "error rescheduling failed block");
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => error!(kind = "db", error = %e, "error reading failed blocks"),
+        }
+        
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,107 @@
+use ethabi::ParamType;
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use crate::abi::token_to_json;
+use crate::error::IndexerError;
+use crate::utils::{bytes_to_hex, keccak256};
+
+//firme incluse nel binario, caricate in function_signatures a ogni avvio
+const BUNDLED: &str = include_str!("../signatures.txt");
+
+
+//chiamata decodificata: metodo, firma e argomenti per nome (argN se il nome non e' noto)
+pub struct DecodedCall {
+    pub name: String,
+    pub signature: String,
+    pub args: Value,
+}
+
+
+//registro le firme incluse nel binario e quelle del file SIGNATURES_FILE, se impostato.
+//restituisce quante firme sono state lette
+pub async fn load(pool: &PgPool, extra_file: Option<&str>) -> Result<usize, IndexerError> {
+    let mut count = save_list(pool, BUNDLED, "bundled").await?;
+    
+    if let Some(path) = extra_file {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| IndexerError::Config(format!("cannot read signatures file {}: {}", path, e)))?;
+        count += save_list(pool, &content, "file").await?;
+    }
+    
+    Ok(count)
+}
+
+
+//una firma per riga (es. transfer(address,uint256)), le righe vuote e quelle con # sono ignorate
+async fn save_list(pool: &PgPool, content: &str, source: &str) -> Result<usize, IndexerError> {
+    let signatures: Vec<String> = content.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect();
+    
+    for signature in &signatures {
+        parse(signature).ok_or_else(|| IndexerError::Config(format!("invalid function signature: {}", signature)))?;
+    }
+    
+    crate::db::save_signatures(pool, &signatures, source).await?;
+    Ok(signatures.len())
+}
+
+
+//selettore della funzione: primi 4 byte del keccak256 della firma
+pub fn selector(signature: &str) -> String {
+    bytes_to_hex(&keccak256(signature.as_bytes())[..4])
+}
+
+
+//decodifico l'input di una transazione con una firma senza nomi dei parametri
+pub fn decode_call(signature: &str, input: &[u8]) -> Option<DecodedCall> {
+    let (name, types) = parse(signature)?;
+    let tokens = ethabi::decode(&types, input.get(4..)?).ok()?;
+    
+    let mut args = Map::new();
+    for (i, token) in tokens.into_iter().enumerate() {
+        args.insert(format!("arg{}", i), token_to_json(token));
+    }
+    
+    Some(DecodedCall {
+        name: name.to_string(),
+        signature: signature.to_string(),
+        args: Value::Object(args),
+    })
+}
+
+
+//nome e tipi da una firma come swap((address,uint256)[],bytes). I tipi vanno separati solo
+//sulle virgole esterne, quelle dentro le tuple fanno parte del tipo
+fn parse(signature: &str) -> Option<(&str, Vec<ParamType>)> {
+    let open = signature.find('(')?;
+    let name = &signature[..open];
+    let inner = signature[open + 1..].strip_suffix(')')?;
+    
+    if name.is_empty() {
+        return None;
+    }
+    if inner.is_empty() {
+        return Some((name, Vec::new()));
+    }
+    
+    let mut types = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(ethabi::param_type::Reader::read(&inner[start..i]).ok()?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    types.push(ethabi::param_type::Reader::read(&inner[start..]).ok()?);
+    
+    Some((name, types))
+}
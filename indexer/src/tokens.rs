@@ -0,0 +1,260 @@
+use crate::error::IndexerError;
+use crate::models::Log;
+use crate::quantity::Quantity;
+
+//keccak256("Transfer(address,address,uint256)"), uguale per ERC-20 e ERC-721
+pub const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+//keccak256("Approval(address,address,uint256)")
+pub const APPROVAL_TOPIC: &str = "0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925";
+
+
+//evento ERC-20 decodificato: (from, to) per Transfer, (owner, spender) per Approval.
+//value e' la stringa decimale dell'uint256, da salvare come NUMERIC
+pub enum Erc20Event {
+    Transfer { from: String, to: String, value: String },
+    Approval { owner: String, spender: String, value: String },
+}
+
+
+//decodifico un log se e' un Transfer o un Approval ERC-20. Gli ERC-721 usano gli stessi topic
+//ma hanno il tokenId indicizzato (4 topic), quindi li riconosco dal numero di topic e dal data
+pub fn decode_erc20(log: &Log) -> Result<Option<Erc20Event>, IndexerError> {
+    if log.topics.len() != 3 || log.data.len() != 2 + 64 {
+        return Ok(None);
+    }
+    
+    let first = topic_to_address(&log.topics[1])?;
+    let second = topic_to_address(&log.topics[2])?;
+    let value = Quantity::from_abi_word(&log.data)?.to_decimal();
+    
+    let event = match log.topics[0].to_lowercase().as_str() {
+        TRANSFER_TOPIC => Erc20Event::Transfer { from: first, to: second, value },
+        APPROVAL_TOPIC => Erc20Event::Approval { owner: first, spender: second, value },
+        _ => return Ok(None),
+    };
+    
+    Ok(Some(event))
+}
+
+
+//un address indicizzato occupa un topic da 32 byte, l'indirizzo sono gli ultimi 20
+pub fn topic_to_address(topic: &str) -> Result<String, IndexerError> {
+    let hex = topic.trim_start_matches("0x");
+    if hex.len() != 64 {
+        return Err(IndexerError::Decode(format!("invalid topic: {}", topic)));
+    }
+    
+    Ok(format!("0x{}", hex[24..].to_lowercase()))
+}
+
+
+//keccak256("TransferSingle(address,address,address,uint256,uint256)")
+pub const TRANSFER_SINGLE_TOPIC: &str = "0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62";
+//keccak256("TransferBatch(address,address,address,uint256[],uint256[])")
+pub const TRANSFER_BATCH_TOPIC: &str = "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb";
+
+
+//trasferimento di un singolo token NFT. Un TransferBatch ERC-1155 diventa piu' trasferimenti,
+//distinti da batch_index. token_id e amount sono stringhe decimali (NUMERIC sul db)
+pub struct NftTransfer {
+    pub standard: &'static str,
+    pub batch_index: i32,
+    pub operator: Option<String>,
+    pub from: String,
+    pub to: String,
+    pub token_id: String,
+    pub amount: String,
+}
+
+
+//decodifico Transfer ERC-721 (tokenId indicizzato, 4 topic), TransferSingle e TransferBatch ERC-1155
+pub fn decode_nft(log: &Log) -> Result<Vec<NftTransfer>, IndexerError> {
+    if log.topics.len() != 4 {
+        return Ok(vec![]);
+    }
+    
+    let topic0 = log.topics[0].to_lowercase();
+    let data = log.data.trim_start_matches("0x");
+    
+    if topic0 == TRANSFER_TOPIC {
+        return Ok(vec![NftTransfer {
+            standard: "erc721",
+            batch_index: 0,
+            operator: None,
+            from: topic_to_address(&log.topics[1])?,
+            to: topic_to_address(&log.topics[2])?,
+            token_id: Quantity::from_abi_word(&log.topics[3])?.to_decimal(),
+            amount: "1".to_string(),
+        }]);
+    }
+    
+    let (ids, amounts) = match topic0.as_str() {
+        TRANSFER_SINGLE_TOPIC => (vec![abi_word(data, 0)?], vec![abi_word(data, 1)?]),
+        TRANSFER_BATCH_TOPIC => (abi_uint_array(data, 0)?, abi_uint_array(data, 1)?),
+        _ => return Ok(vec![]),
+    };
+    
+    if ids.len() != amounts.len() {
+        return Err(IndexerError::Decode(format!("TransferBatch with {} ids and {} values", ids.len(), amounts.len())));
+    }
+    
+    let operator = topic_to_address(&log.topics[1])?;
+    let from = topic_to_address(&log.topics[2])?;
+    let to = topic_to_address(&log.topics[3])?;
+    
+    ids.iter().zip(&amounts).enumerate()
+        .map(|(i, (id, amount))| Ok(NftTransfer {
+            standard: "erc1155",
+            batch_index: i as i32,
+            operator: Some(operator.clone()),
+            from: from.clone(),
+            to: to.clone(),
+            token_id: Quantity::from_abi_word(id)?.to_decimal(),
+            amount: Quantity::from_abi_word(amount)?.to_decimal(),
+        }))
+        .collect()
+}
+
+
+//parola da 32 byte (64 caratteri hex) in posizione `index` nel data ABI senza 0x
+//(offset e lunghezze arrivano dal log, quindi niente aritmetica che possa andare in overflow)
+fn abi_word(data: &str, index: usize) -> Result<&str, IndexerError> {
+    index.checked_mul(64)
+        .and_then(|start| data.get(start..start.checked_add(64)?))
+        .ok_or_else(|| IndexerError::Decode(format!("ABI data too short for word {}", index)))
+}
+
+
+//array dinamico uint256[]: la parola `index` contiene l'offset in byte, li' c'e' la lunghezza
+//seguita dagli elementi
+fn abi_uint_array(data: &str, index: usize) -> Result<Vec<&str>, IndexerError> {
+    let start = word_to_usize(abi_word(data, index)?)? / 32;
+    let len = word_to_usize(abi_word(data, start)?)?;
+    
+    (0..len).map(|i| abi_word(data, start.saturating_add(1 + i))).collect()
+}
+
+
+//offset e lunghezze sono uint256 ma devono stare in un usize
+fn word_to_usize(word: &str) -> Result<usize, IndexerError> {
+    if word[..48].chars().any(|c| c != '0') {
+        return Err(IndexerError::Decode(format!("ABI offset out of range: {}", word)));
+    }
+    Ok(usize::from_str_radix(&word[48..], 16)?)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATOR: &str = "0x0000000000000000000000000000000000000001";
+    const FROM: &str = "0x0000000000000000000000000000000000000002";
+    const TO: &str = "0x0000000000000000000000000000000000000003";
+
+    fn word(n: u64) -> String {
+        format!("{:064x}", n)
+    }
+
+    fn topic(address: &str) -> String {
+        format!("0x{:0>64}", address.trim_start_matches("0x"))
+    }
+
+    fn log(topics: Vec<String>, data: String) -> Log {
+        Log {
+            address: "0x00000000000000000000000000000000000000cc".to_string(),
+            topics,
+            data,
+            log_index: Quantity::from_hex("0x0").unwrap(),
+            transaction_hash: format!("0x{}", word(1)),
+            block_number: Quantity::from_hex("0x1").unwrap(),
+        }
+    }
+
+    fn erc1155(event: &str, data: String) -> Log {
+        log(vec![event.to_string(), topic(OPERATOR), topic(FROM), topic(TO)], data)
+    }
+
+    #[test]
+    fn decode_erc20_transfer() {
+        let log = log(vec![TRANSFER_TOPIC.to_string(), topic(FROM), topic(TO)], format!("0x{}", "f".repeat(64)));
+        match decode_erc20(&log).unwrap() {
+            Some(Erc20Event::Transfer { from, to, value }) => {
+                assert_eq!(from, FROM);
+                assert_eq!(to, TO);
+                assert_eq!(value, "115792089237316195423570985008687907853269984665640564039457584007913129639935");
+            }
+            _ => panic!("expected a Transfer"),
+        }
+    }
+
+    #[test]
+    fn decode_erc20_rejects_malformed_data() {
+        let malformed = log(vec![TRANSFER_TOPIC.to_string(), topic(FROM), topic(TO)], format!("0x{}z", "0".repeat(63)));
+        assert!(decode_erc20(&malformed).is_err());
+
+        //data che non e' una sola parola: non e' un Transfer ERC-20
+        let short = log(vec![TRANSFER_TOPIC.to_string(), topic(FROM), topic(TO)], "0x".to_string());
+        assert!(decode_erc20(&short).unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_erc721_transfer() {
+        let log = log(vec![TRANSFER_TOPIC.to_string(), topic(FROM), topic(TO), format!("0x{}", word(42))], "0x".to_string());
+        let transfers = decode_nft(&log).unwrap();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].standard, "erc721");
+        assert_eq!(transfers[0].token_id, "42");
+        assert_eq!(transfers[0].amount, "1");
+        assert_eq!(transfers[0].operator, None);
+    }
+
+    #[test]
+    fn decode_transfer_single() {
+        let transfers = decode_nft(&erc1155(TRANSFER_SINGLE_TOPIC, format!("0x{}{}", word(7), word(3)))).unwrap();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].operator.as_deref(), Some(OPERATOR));
+        assert_eq!((transfers[0].from.as_str(), transfers[0].to.as_str()), (FROM, TO));
+        assert_eq!((transfers[0].token_id.as_str(), transfers[0].amount.as_str()), ("7", "3"));
+    }
+
+    #[test]
+    fn decode_transfer_single_with_short_data() {
+        assert!(decode_nft(&erc1155(TRANSFER_SINGLE_TOPIC, format!("0x{}", word(7)))).is_err());
+        assert!(decode_nft(&erc1155(TRANSFER_SINGLE_TOPIC, "0x".to_string())).is_err());
+    }
+
+    #[test]
+    fn decode_transfer_batch() {
+        //ids a offset 64: [1, 2], values a offset 160: [10, 20]
+        let data = [64, 160, 2, 1, 2, 2, 10, 20].map(word).concat();
+        let transfers = decode_nft(&erc1155(TRANSFER_BATCH_TOPIC, format!("0x{}", data))).unwrap();
+        let decoded: Vec<_> = transfers.iter()
+            .map(|t| (t.batch_index, t.token_id.as_str(), t.amount.as_str()))
+            .collect();
+        assert_eq!(decoded, [(0, "1", "10"), (1, "2", "20")]);
+    }
+
+    #[test]
+    fn decode_transfer_batch_with_mismatched_arrays() {
+        //ids: [1, 2], values: [10]
+        let data = [64, 160, 2, 1, 2, 1, 10].map(word).concat();
+        assert!(decode_nft(&erc1155(TRANSFER_BATCH_TOPIC, format!("0x{}", data))).is_err());
+    }
+
+    #[test]
+    fn abi_uint_array_rejects_bad_offsets_and_lengths() {
+        //offset oltre la fine del data
+        assert!(abi_uint_array(&[320, 0].map(word).concat(), 0).is_err());
+        //offset che non sta in un usize
+        assert!(abi_uint_array(&format!("{}{}", "f".repeat(64), word(0)), 0).is_err());
+        //offset al limite di usize: start * 64 andrebbe in overflow
+        assert!(abi_uint_array(&[u64::MAX, 0].map(word).concat(), 0).is_err());
+        //lunghezza enorme con pochi elementi
+        assert!(abi_uint_array(&[32, u64::MAX, 1].map(word).concat(), 0).is_err());
+        //lunghezza che punta oltre l'ultimo elemento
+        assert!(abi_uint_array(&[32, 3, 1, 2].map(word).concat(), 0).is_err());
+        //array vuoto
+        assert!(abi_uint_array(&[32, 0].map(word).concat(), 0).unwrap().is_empty());
+    }
+}
@@ -1,7 +1,30 @@
-//funzione per trasformare esadecimale in i64
-pub fn hex_to_i64(hex: &str) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
-    let no_prefix = hex.trim_start_matches("0x");
-
-    Ok(i64::from_str_radix(no_prefix, 16)?)
-}
-
+use crate::error::IndexerError;
+
+//funzione per trasformare una stringa esadecimale (data, bytecode, topic) nei suoi byte
+pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, IndexerError> {
+    let no_prefix = hex.trim_start_matches("0x");
+    if !no_prefix.len().is_multiple_of(2) {
+        return Err(IndexerError::Decode(format!("odd length hex string: {}", hex)));
+    }
+
+    (0..no_prefix.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&no_prefix[i..i + 2], 16)?))
+        .collect()
+}
+
+//funzione per trasformare dei byte nella stringa esadecimale con 0x
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+//keccak256 di una sequenza di byte (hash dei bytecode, selettori delle funzioni)
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    use tiny_keccak::{Hasher, Keccak};
+
+    let mut hasher = Keccak::v256();
+    let mut out = [0u8; 32];
+    hasher.update(bytes);
+    hasher.finalize(&mut out);
+    out
+}